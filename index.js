@@ -15,17 +15,18 @@ const resizeCanvas = (game) => {
   let chunkX = window.innerWidth / 4;
   let chunkY = window.innerHeight / 3;
 
-  if (chunkX <= chunkY) {
-    canvas.width = window.innerWidth;
-    canvas.height = chunkX * 3;
-  } else {
-    canvas.width = chunkY * 4;
-    canvas.height = window.innerHeight;
-  }
-
-  //ctx.scale(1024 / canvas.width, 768 / canvas.height);
-  console.log(ctx.scaleWidth, ctx.scaleHeight);
-  game.resize();
+  let [width, height] = chunkX <= chunkY
+    ? [window.innerWidth, chunkX * 3]
+    : [chunkY * 4, window.innerHeight];
+
+  // Draw at the display's own resolution, and let the game scale to fit
+  let dpi = window.devicePixelRatio || 1;
+  canvas.style.width = width + "px";
+  canvas.style.height = height + "px";
+  canvas.width = Math.floor(width * dpi);
+  canvas.height = Math.floor(height * dpi);
+
+  game.resize(dpi);
 };
 
 class Clock {
@@ -65,7 +66,13 @@ const update = async (game, clock) => {
   } else {
     clock.speak();
 
-    let s = await fetch("./data/" + game.get_successor_args() + ".json").then(r => r.text());
+    let next = game.finish_level();
+    if (next === undefined) {
+      console.log("You finished the campaign!");
+      return;
+    }
+
+    let s = await fetch("./data/" + next + ".json").then(r => r.text());
     game.next_state(s);
     window.setTimeout(() => update(game, new Clock(1000/60)), clock.tick());
   }
@@ -75,8 +82,43 @@ async function init(m) {
   let p = new URLSearchParams(window.location.search);
   let level = p.has("level") ? p.get("level") : "level1";
   let s = await fetch("./data/" + level + ".json").then(r => r.text());
+  let campaign = await fetch("./data/campaign.json").then(r => r.text());
+  let achievements = await fetch("./data/achievements.json").then(r => r.text());
+  let bindings = await fetch("./data/bindings.json").then(r => r.text());
+
+  let game = m.start(level, s, campaign, achievements, bindings, ctx);
+
+  if (p.has("difficulty")) {
+    game.set_difficulty(p.get("difficulty"));
+  }
+
+  if (p.has("name")) {
+    game.set_player_name(p.get("name"));
+  }
 
-  let game = m.start(s, ctx);
+  // e.g. ?players=2&mode=versus
+  if (p.has("players")) {
+    game.set_players(parseInt(p.get("players")), p.has("mode") ? p.get("mode") : "coop");
+  }
+
+  // e.g. ?minimap=topright&minimapsize=240, or ?minimap=off
+  if (p.has("minimap")) {
+    game.set_minimap(p.get("minimap"), p.has("minimapsize") ? parseFloat(p.get("minimapsize")) : 200);
+  }
+
+  // e.g. ?autozoom=off
+  if (p.get("autozoom") === "off") {
+    game.set_auto_zoom(false);
+  }
+
+  if (p.has("twinstick")) {
+    game.set_twin_stick(true);
+  }
+
+  // Straight into the level if there is one in the address
+  if (!p.has("level")) {
+    game.show_main_menu();
+  }
 
   window.addEventListener("resize", () => resizeCanvas(game));
 
@@ -97,7 +139,8 @@ async function init(m) {
   });
 
   document.addEventListener("mousemove", (event) => {
-    game.cursor_moved(event.layerX, event.layerY);
+    let rect = canvas.getBoundingClientRect();
+    game.cursor_moved(event.clientX - rect.left, event.clientY - rect.top);
   });
 
   //var clock = new Clock(1000/60);
@@ -0,0 +1,181 @@
+use std::collections::{HashMap,HashSet};
+
+use crate::broadcast::*;
+use crate::game::Game;
+use crate::ship::DamageSource;
+use crate::storage::*;
+
+const SAVE_KEY: &str = "achievements";
+
+struct Kill {
+    tick: u64,
+    category: usize,
+    bounce: bool,
+}
+
+pub struct Achievements {
+    args: HashMap<String, AchievementArgs>,
+    unlocked: HashSet<String>,
+    // Milestones reached in the current level, for every achievement
+    reached: HashMap<String, Vec<bool>>,
+    kills: Vec<Kill>,
+    // Unlocked since the last call to take_unlocked
+    fresh: Vec<String>,
+    store: Box<dyn Store>,
+}
+
+impl Achievements {
+    pub fn new(args: HashMap<String, AchievementArgs>, store: Box<dyn Store>) -> Achievements {
+        let unlocked = store.load(SAVE_KEY)
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+
+        let mut ret = Achievements {
+            args: args,
+            unlocked: unlocked,
+            reached: HashMap::new(),
+            kills: Vec::new(),
+            fresh: Vec::new(),
+            store: store,
+        };
+
+        ret.start_level();
+        ret
+    }
+
+    pub fn from_json(s: &str, store: Box<dyn Store>) -> serde_json::Result<Achievements> {
+        Ok(Achievements::new(serde_json::from_str(s)?, store))
+    }
+
+    // Forget the progress of the last level
+    pub fn start_level(&mut self) {
+        self.reached = self.args.iter()
+            .map(|(id, a)| (id.to_string(), vec![false; a.when.len()]))
+            .collect();
+        self.kills.clear();
+    }
+
+    // Look at what happened this tick
+    pub fn update(&mut self, game: &Game) {
+        let cast = game.get_broadcast();
+
+        for msg in cast.messages.iter().filter(|m| m.recipient == 0) {
+            if let MessageBody::Death(attribution) = &msg.body {
+                let ship = match game.get_actors().get(&msg.sender) {
+                    Some(ship) if attribution.get_killer().map(|k| game.is_player(k)).unwrap_or(false) => ship,
+                    _ => continue,
+                };
+
+                self.kills.push(Kill {
+                    tick: cast.tick,
+                    category: ship.category,
+                    bounce: matches!(attribution.blow, Some(DamageSource::Asteroid(_))),
+                });
+            }
+        }
+
+        self.check(game, None);
+    }
+
+    // The level is over, check what could only be known at the end
+    pub fn finish(&mut self, level: &str, game: &Game) {
+        self.check(game, Some(level));
+    }
+
+    // Names of achievements unlocked since the last call, for toasts
+    pub fn take_unlocked(&mut self) -> Vec<String> {
+        self.fresh.drain(..).collect()
+    }
+
+    pub fn is_unlocked(&self, id: &str) -> bool {
+        self.unlocked.contains(id)
+    }
+
+    // Every achievement, with whether it is unlocked
+    pub fn list(&self) -> Vec<(AchievementArgs, bool)> {
+        let mut ids: Vec<&String> = self.args.keys().collect();
+        ids.sort();
+
+        ids.iter()
+            .map(|id| (self.args[*id].clone(), self.is_unlocked(id)))
+            .collect()
+    }
+
+    fn check(&mut self, game: &Game, finished: Option<&str>) {
+        let stats = game.get_statistics();
+        let mut unlocked = Vec::new();
+
+        for (id, args) in self.args.iter() {
+            if self.unlocked.contains(id) {
+                continue;
+            }
+
+            let reached = self.reached.get_mut(id).unwrap();
+
+            for (i, milestone) in args.when.iter().enumerate() {
+                reached[i] = reached[i] || match milestone {
+                    MilestoneArgs::Win(level) => finished.is_some() && game.get_victory() == Some(true) &&
+                        level.as_ref().map(|l| Some(l.as_str()) == finished).unwrap_or(true),
+                    MilestoneArgs::Damage(damage) => finished.is_some() && stats.damage_taken <= *damage,
+                    MilestoneArgs::Score(score) => stats.score >= *score,
+                    MilestoneArgs::Combo(combo) => game.get_combo() >= *combo,
+                    MilestoneArgs::TopSpeed(speed) => stats.top_speed >= *speed,
+                    MilestoneArgs::Kills(kills) => count_kills(&self.kills, kills) >= kills.count,
+                };
+            }
+
+            if reached.iter().all(|r| *r) {
+                unlocked.push(id.to_string());
+            }
+        }
+
+        if unlocked.is_empty() {
+            return;
+        }
+
+        for id in unlocked {
+            self.fresh.push(self.args[&id].name.to_string());
+            self.unlocked.insert(id);
+        }
+
+        match serde_json::to_string(&self.unlocked) {
+            Ok(s) => self.store.save(SAVE_KEY, &s),
+            Err(e) => println!("Could not save achievements: {}", e),
+        }
+    }
+}
+
+// Most kills that fit within the window of the milestone
+fn count_kills(kills: &[Kill], args: &KillsArgs) -> usize {
+    let matching: Vec<&Kill> = kills.iter()
+        .filter(|k| args.category.map(|c| c == k.category).unwrap_or(true) && (k.bounce || !args.bounce))
+        .collect();
+
+    matching.iter()
+        .map(|first| matching.iter().filter(|k| k.tick >= first.tick && k.tick - first.tick <= args.within).count())
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::achievement::*;
+    use crate::storage::*;
+
+    #[test]
+    fn test_count_kills() {
+        let kills = vec![
+            Kill { tick: 10, category: 2, bounce: true },
+            Kill { tick: 11, category: 2, bounce: true },
+            Kill { tick: 11, category: 1, bounce: true },
+            Kill { tick: 12, category: 2, bounce: false },
+            Kill { tick: 40, category: 2, bounce: true },
+        ];
+
+        let args: KillsArgs = serde_json::from_str(r#"{"count": 3, "category": 2, "within": 5, "bounce": true}"#).unwrap();
+        assert_eq!(count_kills(&kills, &args), 2);
+
+        let args: KillsArgs = serde_json::from_str(r#"{"count": 3, "within": 5}"#).unwrap();
+        assert_eq!(count_kills(&kills, &args), 4);
+    }
+}
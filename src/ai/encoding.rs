@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use crate::ai::Directive;
+use crate::asteroid::*;
+use crate::physics::{Circle,Point};
+use crate::ship::*;
+use crate::world::World;
+
+use std::f64::consts::TAU;
+
+pub const NEAREST_SHIPS: usize = 4;
+pub const NEAREST_ASTEROIDS: usize = 4;
+
+const SELF_FEATURES: usize = 5;
+const SHIP_FEATURES: usize = 7;
+const ASTEROID_FEATURES: usize = 4;
+
+pub const OBSERVATION_SIZE: usize = SELF_FEATURES
+    + NEAREST_SHIPS * SHIP_FEATURES
+    + NEAREST_ASTEROIDS * ASTEROID_FEATURES;
+
+// Rough scales to keep every feature somewhere around [-1, 1]
+const DISTANCE_SCALE: f64 = 1024.0;
+const SPEED_SCALE: f64 = 400.0;
+
+pub const DISCRETE_ACTIONS: usize = 7;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Action {
+    // 0: idle, 1: thrust, 2: rotate left, 3: rotate right,
+    // 4: thrust and rotate left, 5: thrust and rotate right, 6: brake
+    Discrete(usize),
+    // Thrust in [0, 1] and rotation in [-1, 1] full turns per second
+    Continuous(f64, f64),
+}
+
+impl Action {
+    pub fn directives(&self, time_delta: f64) -> Vec<Directive> {
+        match *self {
+            Action::Discrete(n) => match n {
+                1 => vec![Directive::Thrust(1.0)],
+                2 => vec![Directive::Rotate(-TAU * time_delta)],
+                3 => vec![Directive::Rotate(TAU * time_delta)],
+                4 => vec![Directive::Rotate(-TAU * time_delta), Directive::Thrust(1.0)],
+                5 => vec![Directive::Rotate(TAU * time_delta), Directive::Thrust(1.0)],
+                6 => vec![Directive::Brake],
+                _ => vec![],
+            },
+            Action::Continuous(thrust, rotation) => {
+                let mut ret = Vec::new();
+                let rotation = rotation.clamp(-1.0, 1.0);
+                let thrust = thrust.clamp(0.0, 1.0);
+
+                if rotation != 0.0 {
+                    ret.push(Directive::Rotate(TAU * rotation * time_delta));
+                }
+                if thrust > 0.0 {
+                    ret.push(Directive::Thrust(thrust));
+                }
+
+                ret
+            },
+        }
+    }
+}
+
+// Describe the world as seen from ship `id` as a fixed-size vector. Positions
+// and velocities are relative to the observer, and the nearest ships and
+// asteroids come first. Missing entries are padded with zeros.
+pub fn observe(id: u32, player_id: Option<u32>, world: Option<&World>, actors: &HashMap<u32, ShipCache>, props: &[Asteroid]) -> Vec<f64> {
+    let mut ret = Vec::with_capacity(OBSERVATION_SIZE);
+
+    let me = match actors.get(&id) {
+        Some(me) => me,
+        None => {
+            ret.resize(OBSERVATION_SIZE, 0.0);
+            return ret;
+        },
+    };
+    let position = me.get_point();
+
+    // The short way round, in a wrapping world
+    let nearest = |p: Point| world.map_or(p, |w| w.nearest(position, p));
+
+    ret.push(me.vector.get_dx() / SPEED_SCALE);
+    ret.push(me.vector.get_dy() / SPEED_SCALE);
+    ret.push(me.direction.cos());
+    ret.push(me.direction.sin());
+    ret.push(me.health.max(0.0) / HEALTH[me.category]);
+
+    let mut ships: Vec<&ShipCache> = actors.values()
+        .filter(|a| a.id != id && a.health > 0.0)
+        .collect();
+    ships.sort_by(|a, b| position.distance(nearest(a.get_point())).partial_cmp(&position.distance(nearest(b.get_point()))).unwrap());
+
+    for i in 0..NEAREST_SHIPS {
+        match ships.get(i) {
+            Some(ship) => {
+                let delta = nearest(ship.get_point()) - position;
+                ret.push(1.0);
+                ret.push(delta.x / DISTANCE_SCALE);
+                ret.push(delta.y / DISTANCE_SCALE);
+                ret.push((ship.vector.get_dx() - me.vector.get_dx()) / SPEED_SCALE);
+                ret.push((ship.vector.get_dy() - me.vector.get_dy()) / SPEED_SCALE);
+                ret.push(ship.health.max(0.0) / HEALTH[ship.category]);
+                ret.push(if Some(ship.id) == player_id { 1.0 } else { 0.0 });
+            },
+            None => ret.extend_from_slice(&[0.0; SHIP_FEATURES]),
+        }
+    }
+
+    let mut asteroids: Vec<Circle> = props.iter()
+        .map(|a| world.map_or(a.get_circle(), |w| w.nearest_circle(position, a.get_circle())))
+        .collect();
+    asteroids.sort_by(|a, b| {
+        (position.distance(Point::new(a.x, a.y)) - a.r)
+            .partial_cmp(&(position.distance(Point::new(b.x, b.y)) - b.r))
+            .unwrap()
+    });
+
+    for i in 0..NEAREST_ASTEROIDS {
+        match asteroids.get(i) {
+            Some(circle) => {
+                ret.push(1.0);
+                ret.push((circle.x - position.x) / DISTANCE_SCALE);
+                ret.push((circle.y - position.y) / DISTANCE_SCALE);
+                ret.push(circle.r / DISTANCE_SCALE);
+            },
+            None => ret.extend_from_slice(&[0.0; ASTEROID_FEATURES]),
+        }
+    }
+
+    ret
+}
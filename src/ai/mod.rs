@@ -0,0 +1,529 @@
+pub mod encoding;
+pub mod network;
+pub mod script;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use serde::Deserialize;
+
+use crate::ai::network::{Network,NeuralBrain,load_network};
+use crate::ai::script::{Script,ScriptBrain,load_script};
+use crate::asteroid::*;
+use crate::broadcast::*;
+use crate::input::Action;
+use crate::physics::{Circle,Point,Vector};
+use crate::ship::*;
+
+use std::f64::consts::{E,PI,FRAC_PI_2,TAU};
+
+pub fn build_brain(category: usize, id: u32) -> Box<dyn Brain> {
+    match category {
+        BELL => Box::new(BellBrain::new(id)),
+        JALAPENO => Box::new(JalapenoBrain::new(id)),
+        CAYENNE => Box::new(CayenneBrain::new(id)),
+        CHICKPEA => Box::new(ChickpeaBrain::new(id)),
+        _ => panic!("Invalid int: {:}", category),
+    }
+}
+
+// Brains that can be assigned to ships from level files, in place of the
+// default brain for their category.
+#[derive(Clone,Debug,Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BrainArgs {
+    // The default brain of another category
+    Builtin(usize),
+    Script(#[serde(deserialize_with = "load_script")] Script),
+    Network(#[serde(deserialize_with = "load_network")] Network),
+    // Follows the player around, for allies
+    Escort,
+}
+
+impl BrainArgs {
+    pub fn build(&self, id: u32) -> Box<dyn Brain> {
+        match self {
+            BrainArgs::Builtin(category) => build_brain(*category, id),
+            BrainArgs::Script(script) => Box::new(ScriptBrain::new(id, script.clone())),
+            BrainArgs::Network(network) => Box::new(NeuralBrain::new(id, network.clone())),
+            BrainArgs::Escort => Box::new(EscortBrain::new(id)),
+        }
+    }
+}
+
+// Rotations are tiny per tick, so the debug overlay draws them this much larger
+pub const ROTATE_SCALE: f64 = 20.0;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Directive {
+    SetDirection(f64),
+    Rotate(f64),
+    Thrust(f64),
+    Brake,
+    Aim(Point)
+}
+
+// What a brain is thinking, for the debug overlay. Steering vectors are
+// only directions, and are all drawn with the same length.
+#[derive(Clone,Debug,Default)]
+pub struct DebugInfo {
+    pub state: String,
+    pub perception: Option<f64>,
+    pub target: Option<Point>,
+    pub path: Vec<Point>,
+    pub steering: Vec<Vector>,
+}
+
+impl DebugInfo {
+    pub fn new(state: &str) -> DebugInfo {
+        DebugInfo {
+            state: state.to_string(),
+            ..DebugInfo::default()
+        }
+    }
+}
+
+pub trait Brain {
+    fn think(&mut self, time_delta: f64, cast: &Broadcast, actors: &HashMap<u32, ShipCache>, props: &Vec<Asteroid>) -> Vec<Directive>;
+
+    // Brains may explain themselves to the debug overlay
+    fn debug_info(&self) -> Option<DebugInfo> {
+        None
+    }
+
+    fn target_visible(&self, target: Point, me: Point, cast: &Broadcast, props: &[Asteroid]) -> bool {
+        let path = cast.sight_line(me, target);
+
+        for prop in props.iter() {
+            if cast.blocks(&path, &prop.get_circle()) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    // Has the target been in range and in sight for long enough to react to it?
+    fn react(&self, spotted: &mut Option<u64>, target: Point, me: Point, cast: &Broadcast, props: &[Asteroid]) -> bool {
+        if cast.distance(me, target) > cast.difficulty.perception() || !self.target_visible(target, me, cast, props) {
+            *spotted = None;
+            return false;
+        }
+
+        let tick = *spotted.get_or_insert(cast.tick);
+
+        cast.tick - tick >= cast.difficulty.reaction_delay()
+    }
+}
+
+impl std::fmt::Debug for dyn Brain {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "I am a brain.")
+    }
+}
+
+// Does whatever it is told from the outside, e.g. by a training loop
+#[derive(Clone,Debug)]
+pub struct PuppetBrain {
+    directives: Rc<RefCell<Vec<Directive>>>,
+}
+
+impl PuppetBrain {
+    pub fn new(directives: Rc<RefCell<Vec<Directive>>>) -> PuppetBrain {
+        PuppetBrain {
+            directives: directives,
+        }
+    }
+}
+
+impl Brain for PuppetBrain {
+    fn think(&mut self, time_delta: f64, cast: &Broadcast, actors: &HashMap<u32, ShipCache>, props: &Vec<Asteroid>) -> Vec<Directive> {
+        self.directives.borrow().to_vec()
+    }
+}
+
+// Distance from the cursor at which the player seeks it at full thrust
+const SEEK_DISTANCE: f64 = 200.0;
+
+#[derive(Clone,Debug)]
+pub struct BellBrain {
+    id: u32,
+    previous_collisons: Vec<u32>,
+}
+
+impl BellBrain {
+    pub fn new(id: u32) -> BellBrain {
+        BellBrain {
+            id: id,
+            previous_collisons: Vec::new(),
+        }
+    }
+}
+
+impl Brain for BellBrain {
+    fn think(&mut self, time_delta: f64, cast: &Broadcast, actors: &HashMap<u32, ShipCache>, props: &Vec<Asteroid>) -> Vec<Directive> {
+        let input = cast.get_input(self.id);
+
+        if input.is_held(Action::Seek) {
+            // Ease off when closing in on the cursor
+            let distance = cast.distance(actors[&self.id].get_point(), cast.cursor);
+            vec![Directive::Aim(cast.cursor), Directive::Thrust((distance / SEEK_DISTANCE).min(1.0))]
+        }
+        else {
+            let mut ret = Vec::<Directive>::new();
+
+            match input.get_aim() {
+                Some(direction) => ret.push(Directive::SetDirection(direction)),
+                None if input.get_rotation() != 0.0 => ret.push(Directive::Rotate(TAU * input.get_rotation() * time_delta)),
+                None => (),
+            }
+            if input.get_thrust() > 0.0 {
+                ret.push(Directive::Thrust(input.get_thrust()));
+            }
+            if input.is_braking() {
+                ret.push(Directive::Brake);
+            }
+
+            ret
+        }
+    }
+}
+
+#[derive(Clone,Debug)]
+pub struct JalapenoBrain {
+    id: u32,
+    active: bool,
+    spotted: Option<u64>,
+    perception: f64,
+    player_position: Point,
+    previous_collisons: Vec<u32>,
+}
+
+impl JalapenoBrain {
+    pub fn new(id: u32) -> JalapenoBrain {
+        JalapenoBrain {
+            id: id,
+            active: false,
+            spotted: None,
+            perception: f64::INFINITY,
+            player_position: Point::new(0.0, 0.0),
+            previous_collisons: Vec::new(),
+        }
+    }
+}
+
+impl Brain for JalapenoBrain {
+    fn think(&mut self, time_delta: f64, cast: &Broadcast, actors: &HashMap<u32, ShipCache>, props: &Vec<Asteroid>) -> Vec<Directive> {
+        let mut spotted = self.spotted;
+        self.perception = cast.difficulty.perception();
+
+        let me = actors[&self.id].get_point();
+
+        if let Some((_, target)) = cast.nearest_player(me) {
+            if self.react(&mut spotted, target, me, cast, props) {
+                self.active = true;
+                self.player_position = target;
+            }
+        }
+
+        self.spotted = spotted;
+
+        return match self.active {
+            true => vec![Directive::Aim(self.player_position), Directive::Thrust(1.0)],
+            false => vec![Directive::Rotate(FRAC_PI_2 * time_delta)],
+        }
+    }
+
+    fn debug_info(&self) -> Option<DebugInfo> {
+        Some(match self.active {
+            true => DebugInfo {
+                target: Some(self.player_position),
+                ..DebugInfo::new("ramming")
+            },
+            false => DebugInfo {
+                perception: Some(self.perception).filter(|p| p.is_finite()),
+                ..DebugInfo::new(if self.spotted.is_some() { "spotted" } else { "searching" })
+            },
+        })
+    }
+}
+
+#[derive(Clone,Debug)]
+pub struct CayenneBrain {
+    id: u32,
+    active: bool,
+    spotted: Option<u64>,
+    perception: f64,
+    player_position: Point,
+    previous_collisons: Vec<u32>,
+    steering: Vec<Vector>,
+}
+
+impl CayenneBrain {
+    pub fn new(id: u32) -> CayenneBrain {
+        CayenneBrain {
+            id: id,
+            active: false,
+            spotted: None,
+            perception: f64::INFINITY,
+            player_position: Point::new(0.0, 0.0),
+            previous_collisons: Vec::new(),
+            steering: Vec::new(),
+        }
+    }
+
+    pub fn chase(&mut self, time_delta: f64, me: &ShipCache, target: Point) -> Vec<Directive> {
+        // How to get to target (from where we are now)
+        let ideal = Vector::from(target - me.get_point());
+        
+        // Offsetting the sine is important to avoid situations like mobs orbiting the target
+        let delta = ideal.radian_delta(me.vector.direction);
+        let sinimized = Vector::new(me.vector.direction - FRAC_PI_2, me.vector.magnitude * delta.sin());
+        
+        // Don't focus too much on the offset, the priority should be chasing the target
+        let multiplier = 1.0 / (1.0 + E.powf(-delta.abs()));
+        let plan = Vector::new(ideal.direction, delta.cos().abs()) * multiplier + sinimized;
+
+        self.steering = vec![ideal, sinimized, plan];
+
+        vec![
+            Directive::SetDirection(plan.direction),
+            Directive::Thrust(1.0)
+        ]
+    }
+
+    fn manhattan_chase(&mut self, time_delta: f64, me: &ShipCache, target: &ShipCache) -> Vec<Directive> {
+        let mp = me.get_point();
+        let tp = target.get_point();
+        let distance = tp - mp;
+
+        let x_comp = distance.x - me.vector.get_dx();
+        let y_comp = distance.y - me.vector.get_dy();
+
+        let plan = Vector::from_deltas(x_comp, y_comp);
+
+        println!("yolo");
+        vec![
+            Directive::SetDirection(plan.direction),
+            Directive::Thrust(1.0)
+        ]
+    }
+}
+
+impl Brain for CayenneBrain {
+    fn think(&mut self, time_delta: f64, cast: &Broadcast, actors: &HashMap<u32, ShipCache>, props: &Vec<Asteroid>) -> Vec<Directive> {
+        let mut spotted = self.spotted;
+        self.perception = cast.difficulty.perception();
+
+        let nearest = cast.nearest_player(actors[&self.id].get_point());
+
+        if let Some((_, target)) = nearest {
+            if self.react(&mut spotted, target, actors[&self.id].get_point(), cast, props) {
+                self.active = true;
+                self.player_position = target;
+            }
+        }
+
+        self.spotted = spotted;
+
+        match (self.active, nearest) {
+            (true, Some((_, target))) => self.chase(time_delta, &actors[&self.id], target),
+            _ => vec![Directive::Rotate(FRAC_PI_2 * time_delta)],
+        }
+    }
+
+    fn debug_info(&self) -> Option<DebugInfo> {
+        Some(match self.active {
+            true => DebugInfo {
+                target: Some(self.player_position),
+                steering: self.steering.to_vec(),
+                ..DebugInfo::new("chasing")
+            },
+            false => DebugInfo {
+                perception: Some(self.perception).filter(|p| p.is_finite()),
+                ..DebugInfo::new(if self.spotted.is_some() { "spotted" } else { "searching" })
+            },
+        })
+    }
+}
+
+#[derive(Clone,Debug)]
+pub struct ChickpeaBrain {
+    id: u32,
+    active: bool,
+    spotted: Option<u64>,
+    perception: f64,
+    previous_collisons: Vec<u32>,
+    target: Option<Point>,
+    steering: Vector,
+}
+
+impl ChickpeaBrain {
+    fn new(id: u32) -> ChickpeaBrain {
+        ChickpeaBrain {
+            id: id,
+            active: false,
+            spotted: None,
+            perception: f64::INFINITY,
+            previous_collisons: Vec::new(),
+            target: None,
+            steering: Vector::empty(),
+        }
+    }
+
+    fn chase(&mut self, cast: &Broadcast, actors: &HashMap<u32, ShipCache>, target_id: &u32) -> Vector {
+        let me = actors[&self.id].get_point();
+        let target = cast.nearest_circle(me, actors[target_id].circle);
+        let speed = actors[&self.id].vector.magnitude.abs();
+        let horizon = (speed / FORCE[CHICKPEA]).max(FORCE[CHICKPEA] * 4.0);
+
+        actors.iter()
+        .filter(
+            // Only separate from your own class
+            |(id, ship)| match ship.category {
+                CHICKPEA => true,
+                _ => false
+            } &&
+            // Who are not me
+            self.id != ship.id &&
+            // No further than 25 pixels away
+            cast.distance(me, ship.get_point()) - RADIUS[CHICKPEA] <= horizon &&
+            // If the target is between another ship, don't bother
+            !cast.blocks(&cast.sight_line(me, ship.get_point()), &target)
+        )
+        .fold(Vector::from(Point::new(target.x, target.y) - me), |sum, (id, ship)| {
+            sum + Vector::from(me - cast.nearest(me, ship.get_point()))
+        })
+    }
+}
+
+impl Brain for ChickpeaBrain {
+    fn think(&mut self, time_delta: f64, cast: &Broadcast, actors: &HashMap<u32, ShipCache>, props: &Vec<Asteroid>) -> Vec<Directive> {
+        let me = actors[&self.id].get_point();
+
+        let (id, target) = match cast.nearest_player(me) {
+            Some(player) => player,
+            None => return vec![],
+        };
+
+        // Flocks wait to spot the player like everyone else
+        let mut spotted = self.spotted;
+        self.perception = cast.difficulty.perception();
+
+        if self.react(&mut spotted, target, me, cast, props) {
+            self.active = true;
+        }
+
+        self.spotted = spotted;
+
+        if !self.active {
+            return vec![];
+        }
+
+        let d = self.chase(cast, actors, &id);
+
+        self.target = Some(target);
+        self.steering = d;
+
+        if d.magnitude > 0.0 {
+            return vec![
+                Directive::SetDirection(d.direction),
+                Directive::Thrust(1.0)
+            ];
+        }
+
+        vec![]//*Directive::Aim(cast.player_position),*/ Directive::Thrust(1.0 * time_delta)]
+    }
+
+    fn debug_info(&self) -> Option<DebugInfo> {
+        Some(match self.active {
+            true => DebugInfo {
+                target: self.target,
+                steering: vec![self.steering],
+                ..DebugInfo::new("flocking")
+            },
+            false => DebugInfo {
+                perception: Some(self.perception).filter(|p| p.is_finite()),
+                ..DebugInfo::new(if self.spotted.is_some() { "spotted" } else { "searching" })
+            },
+        })
+    }
+}
+
+// Keeps close to the player, without getting in the way
+#[derive(Clone,Debug)]
+pub struct EscortBrain {
+    id: u32,
+    following: bool,
+    target: Option<Point>,
+}
+
+impl EscortBrain {
+    pub fn new(id: u32) -> EscortBrain {
+        EscortBrain {
+            id: id,
+            following: false,
+            target: None,
+        }
+    }
+}
+
+impl Brain for EscortBrain {
+    fn think(&mut self, time_delta: f64, cast: &Broadcast, actors: &HashMap<u32, ShipCache>, props: &Vec<Asteroid>) -> Vec<Directive> {
+        let me = actors[&self.id].get_point();
+
+        let target = match cast.nearest_player(me) {
+            Some((_, target)) => target,
+            None => {
+                self.following = false;
+                self.target = None;
+                return vec![];
+            },
+        };
+
+        self.target = Some(target);
+
+        // Catch up when too far behind, hold still when close enough
+        self.following = cast.distance(me, target) > 150.0;
+
+        match self.following {
+            true => vec![Directive::Aim(target), Directive::Thrust(1.0)],
+            false => vec![Directive::Brake],
+        }
+    }
+
+    fn debug_info(&self) -> Option<DebugInfo> {
+        Some(DebugInfo {
+            target: self.target,
+            ..DebugInfo::new(match self.following {
+                true => "following",
+                false => "waiting",
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::*;
+    use crate::input::{Controls,InputState};
+
+    #[test]
+    fn test_partial_thrust() {
+        let mut controls = Controls::from_json(r#"{
+            "players": [{"gamepad": 0}],
+            "triggers": {"7": {"axis": "thrust"}}
+        }"#, 1).unwrap();
+
+        // A trigger pulled part of the way
+        controls.trigger_moved(0, 7, 0.4);
+
+        let inputs = controls.tick();
+        let mut cast = Broadcast::new();
+        cast.set_input(1, InputState::from_inputs(&inputs[0], &InputState::new(), Point::new(0.0, 0.0)));
+
+        let mut brain = BellBrain::new(1);
+        assert_eq!(brain.think(1.0 / 60.0, &cast, &HashMap::new(), &vec![]), vec![Directive::Thrust(0.4)]);
+    }
+}
@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+use serde::{de,Deserialize,Deserializer};
+
+use crate::ai::{Brain, DebugInfo, Directive};
+use crate::ai::encoding::*;
+use crate::asteroid::*;
+use crate::broadcast::*;
+use crate::ship::*;
+
+// Binary weight files start with this, followed by the output kind (u8),
+// the number of layers (u32) and then for every layer: inputs (u32),
+// outputs (u32), activation (u8), weights (f32, one row per output) and
+// biases (f32). Everything is little-endian.
+const MAGIC: &[u8; 6] = b"WAMSNN";
+
+#[derive(Clone,Copy,Debug,Default,Deserialize,PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Activation {
+    Linear,
+    #[default]
+    Relu,
+    Sigmoid,
+    Tanh,
+}
+
+impl Activation {
+    fn from_byte(b: u8) -> Option<Activation> {
+        match b {
+            0 => Some(Activation::Linear),
+            1 => Some(Activation::Relu),
+            2 => Some(Activation::Sigmoid),
+            3 => Some(Activation::Tanh),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, x: f64) -> f64 {
+        match self {
+            Activation::Linear => x,
+            Activation::Relu => x.max(0.0),
+            Activation::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+            Activation::Tanh => x.tanh(),
+        }
+    }
+}
+
+// How the last layer maps onto actions, see encoding::Action
+#[derive(Clone,Copy,Debug,Default,Deserialize,PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Output {
+    #[default]
+    Discrete,
+    Continuous,
+}
+
+#[derive(Clone,Debug,Deserialize)]
+pub struct Layer {
+    pub weights: Vec<Vec<f64>>,
+    pub biases: Vec<f64>,
+    #[serde(default)]
+    pub activation: Activation,
+}
+
+impl Layer {
+    fn evaluate(&self, input: &[f64]) -> Vec<f64> {
+        self.weights.iter()
+            .zip(self.biases.iter())
+            .map(|(row, bias)| {
+                let sum = row.iter().zip(input).fold(*bias, |sum, (w, x)| sum + w * x);
+                self.activation.apply(sum)
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone,Debug,Deserialize)]
+pub struct Network {
+    pub layers: Vec<Layer>,
+    #[serde(default)]
+    pub output: Output,
+}
+
+impl Network {
+    pub fn from_json(s: &str) -> Result<Network, String> {
+        let network: Network = serde_json::from_str(s).map_err(|e| e.to_string())?;
+        network.validate()?;
+
+        Ok(network)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Network, String> {
+        if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+            return Err("Not a network file.".to_string());
+        }
+
+        let mut reader = Reader { bytes: bytes, position: MAGIC.len() };
+
+        let output = match reader.u8()? {
+            0 => Output::Discrete,
+            1 => Output::Continuous,
+            n => return Err(format!("Unknown output kind {}.", n)),
+        };
+
+        let mut layers = Vec::new();
+
+        for _ in 0..reader.u32()? {
+            let inputs = reader.u32()? as usize;
+            let outputs = reader.u32()? as usize;
+            let activation = Activation::from_byte(reader.u8()?).ok_or("Unknown activation.")?;
+
+            // Sizes come from the file, so check them before allocating anything
+            match inputs.checked_add(1).and_then(|n| n.checked_mul(outputs)).and_then(|n| n.checked_mul(4)) {
+                Some(n) if n <= reader.remaining() => (),
+                _ => return Err("Network file ended early.".to_string()),
+            }
+
+            let mut weights = Vec::with_capacity(outputs);
+            for _ in 0..outputs {
+                weights.push((0..inputs).map(|_| reader.f32()).collect::<Result<Vec<f64>, String>>()?);
+            }
+            let biases = (0..outputs).map(|_| reader.f32()).collect::<Result<Vec<f64>, String>>()?;
+
+            layers.push(Layer {
+                weights: weights,
+                biases: biases,
+                activation: activation,
+            });
+        }
+
+        let network = Network { layers: layers, output: output };
+        network.validate()?;
+
+        Ok(network)
+    }
+
+    // Read either format from disk
+    pub fn load(filename: &str) -> Result<Network, String> {
+        let bytes = std::fs::read(filename).map_err(|e| format!("{}: {}", filename, e))?;
+
+        match bytes.starts_with(MAGIC) {
+            true => Network::from_bytes(&bytes),
+            false => Network::from_json(&String::from_utf8_lossy(&bytes)),
+        }
+    }
+
+    // Make sure the network fits the observation encoding and action space
+    pub fn validate(&self) -> Result<(), String> {
+        let mut size = OBSERVATION_SIZE;
+
+        for (i, layer) in self.layers.iter().enumerate() {
+            if layer.weights.len() != layer.biases.len() {
+                return Err(format!("Layer {} has {} rows of weights, but {} biases.", i, layer.weights.len(), layer.biases.len()));
+            }
+
+            if let Some(row) = layer.weights.iter().find(|row| row.len() != size) {
+                return Err(format!("Layer {} expects {} inputs, got {}.", i, row.len(), size));
+            }
+
+            size = layer.biases.len();
+        }
+
+        let expected = match self.output {
+            Output::Discrete => DISCRETE_ACTIONS,
+            Output::Continuous => 2,
+        };
+
+        if size != expected {
+            return Err(format!("Network has {} outputs, expected {}.", size, expected));
+        }
+
+        Ok(())
+    }
+
+    pub fn evaluate(&self, input: &[f64]) -> Vec<f64> {
+        self.layers.iter().fold(input.to_vec(), |x, layer| layer.evaluate(&x))
+    }
+
+    pub fn act(&self, input: &[f64]) -> Action {
+        let output = self.evaluate(input);
+
+        match self.output {
+            Output::Discrete => Action::Discrete(
+                output.iter()
+                    .enumerate()
+                    .fold((0, f64::MIN), |best, (i, x)| if *x > best.1 { (i, *x) } else { best })
+                    .0
+            ),
+            Output::Continuous => Action::Continuous(output[0], output[1]),
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self.position + n;
+
+        if end > self.bytes.len() {
+            return Err("Network file ended early.".to_string());
+        }
+
+        let ret = &self.bytes[self.position..end];
+        self.position = end;
+
+        Ok(ret)
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Result<f64, String> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()) as f64)
+    }
+}
+
+// Where a level finds the weights: a path to a weights file, or the network itself
+#[derive(Clone,Debug,Deserialize)]
+#[serde(untagged)]
+pub enum NetworkArgs {
+    File(String),
+    Inline(Network),
+}
+
+impl NetworkArgs {
+    pub fn load(&self) -> Result<Network, String> {
+        match self {
+            NetworkArgs::File(filename) => Network::load(filename),
+            NetworkArgs::Inline(network) => network.validate().map(|_| network.clone()),
+        }
+    }
+}
+
+// Networks are read and checked while the level loads, so a bad one is an
+// error from the level instead of a crash when the first ship spawns
+pub fn load_network<'de, D>(deserializer: D) -> Result<Network, D::Error>
+    where D: Deserializer<'de>
+{
+    NetworkArgs::deserialize(deserializer)?
+        .load()
+        .map_err(|e| de::Error::custom(format!("Invalid network: {}", e)))
+}
+
+pub struct NeuralBrain {
+    id: u32,
+    network: Network,
+    action: Option<Action>,
+}
+
+impl NeuralBrain {
+    pub fn new(id: u32, network: Network) -> NeuralBrain {
+        NeuralBrain {
+            id: id,
+            network: network,
+            action: None,
+        }
+    }
+}
+
+impl Brain for NeuralBrain {
+    fn think(&mut self, time_delta: f64, cast: &Broadcast, actors: &HashMap<u32, ShipCache>, props: &Vec<Asteroid>) -> Vec<Directive> {
+        let observation = observe(self.id, cast.player_id, cast.world.as_ref(), actors, props);
+        let action = self.network.act(&observation);
+
+        self.action = Some(action);
+        action.directives(time_delta)
+    }
+
+    fn debug_info(&self) -> Option<DebugInfo> {
+        Some(DebugInfo::new(&match self.action {
+            Some(action) => format!("{:?}", action),
+            None => "waiting".to_string(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::encoding::*;
+    use crate::ai::network::*;
+
+    fn bytes(output: u8, layers: &[(usize, usize, u8, f32)]) -> Vec<u8> {
+        let mut ret = MAGIC.to_vec();
+        ret.push(output);
+        ret.extend_from_slice(&(layers.len() as u32).to_le_bytes());
+
+        for &(inputs, outputs, activation, value) in layers {
+            ret.extend_from_slice(&(inputs as u32).to_le_bytes());
+            ret.extend_from_slice(&(outputs as u32).to_le_bytes());
+            ret.push(activation);
+
+            for _ in 0..(inputs + 1) * outputs {
+                ret.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        ret
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let network = Network::from_bytes(&bytes(1, &[(OBSERVATION_SIZE, 3, 0, 0.5), (3, 2, 0, 1.0)])).unwrap();
+        let input = vec![1.0; OBSERVATION_SIZE];
+        let hidden = 0.5 * OBSERVATION_SIZE as f64 + 0.5;
+
+        assert_eq!(network.evaluate(&input), vec![hidden * 3.0 + 1.0; 2]);
+        assert_eq!(network.act(&input), Action::Continuous(hidden * 3.0 + 1.0, hidden * 3.0 + 1.0));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Network::from_bytes(&bytes(0, &[(OBSERVATION_SIZE, 2, 1, 1.0)])).is_err());
+        assert!(Network::from_bytes(&bytes(0, &[(3, DISCRETE_ACTIONS, 1, 1.0)])).is_err());
+        assert!(Network::from_bytes(&bytes(0, &[(OBSERVATION_SIZE, DISCRETE_ACTIONS, 1, 1.0)])).is_ok());
+        assert!(Network::from_bytes(b"WAMSNN").is_err());
+
+        // A header promising more weights than the file holds
+        let mut huge = bytes(0, &[]);
+        huge[7..11].copy_from_slice(&1u32.to_le_bytes());
+        huge.extend_from_slice(&u32::MAX.to_le_bytes());
+        huge.extend_from_slice(&u32::MAX.to_le_bytes());
+        huge.push(1);
+        assert!(Network::from_bytes(&huge).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use rhai::{Array, CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+use serde::{de,Deserialize,Deserializer};
+
+use crate::ai::{Brain, DebugInfo, Directive};
+use crate::asteroid::*;
+use crate::broadcast::*;
+use crate::physics::{Circle,Point};
+use crate::ship::*;
+use crate::world::World;
+
+// Scripts only get to see what is within this distance of the ship
+const PERCEPTION: f64 = 1000.0;
+const MAX_OPERATIONS: u64 = 100_000;
+
+// A script has to define a function like this:
+//
+//     fn think(me, cast, actors, asteroids, time_delta) {
+//         [aim(cast.player_position.x, cast.player_position.y), thrust(1.0)]
+//     }
+//
+// and return an array of directives, built with thrust(m), rotate(d),
+// set_direction(d), aim(x, y) and brake(). Anything stored on `this` is
+// kept between ticks, e.g. `this.seen_player = true;`. The debug overlay
+// shows `this.state`, `this.target` and `this.path` if they are set.
+// `cast.world` has the level's width, height and edge ("open", "solid"
+// or "wrap"), or is () for levels without bounds.
+#[derive(Clone,Debug)]
+pub struct Script {
+    // Shared by every ship with the same brain
+    engine: Rc<Engine>,
+    ast: Rc<AST>,
+}
+
+impl Script {
+    pub fn compile(source: &str) -> Result<Script, String> {
+        let engine = build_engine();
+        let ast = engine.compile(source).map_err(|e| e.to_string())?;
+
+        Ok(Script {
+            engine: Rc::new(engine),
+            ast: Rc::new(ast),
+        })
+    }
+}
+
+// Scripts are compiled while the level loads, so one that doesn't is an
+// error from the level, as with networks
+pub fn load_script<'de, D>(deserializer: D) -> Result<Script, D::Error>
+    where D: Deserializer<'de>
+{
+    let source = String::deserialize(deserializer)?;
+
+    Script::compile(&source)
+        .map_err(|e| de::Error::custom(format!("Invalid script: {}", e)))
+}
+
+pub struct ScriptBrain {
+    id: u32,
+    // None once the script has failed
+    script: Option<Script>,
+    scope: Scope<'static>,
+    memory: Dynamic,
+}
+
+impl ScriptBrain {
+    pub fn new(id: u32, script: Script) -> ScriptBrain {
+        ScriptBrain {
+            id: id,
+            script: Some(script),
+            scope: Scope::new(),
+            memory: Dynamic::from(Map::new()),
+        }
+    }
+}
+
+impl Brain for ScriptBrain {
+    fn think(&mut self, time_delta: f64, cast: &Broadcast, actors: &HashMap<u32, ShipCache>, props: &Vec<Asteroid>) -> Vec<Directive> {
+        let script = match &self.script {
+            Some(script) => script,
+            None => return vec![],
+        };
+
+        let me = &actors[&self.id];
+        let position = me.get_point();
+
+        // Where everything is as seen from here, which in a wrapping world
+        // may be across an edge
+        let nearby: Array = actors.values()
+            .filter(|a| a.id != self.id && cast.distance(position, a.get_point()) <= PERCEPTION)
+            .map(|a| Dynamic::from(ship_to_map(a, cast.nearest(position, a.get_point()))))
+            .collect();
+
+        let asteroids: Array = props.iter()
+            .map(|p| cast.nearest_circle(position, p.get_circle()))
+            .filter(|c| Point::new(c.x, c.y).distance(position) <= PERCEPTION)
+            .map(|c| Dynamic::from(circle_to_map(&c)))
+            .collect();
+
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.memory);
+
+        let result = script.engine.call_fn_with_options::<Array>(
+            options, &mut self.scope, &script.ast, "think",
+            (ship_to_map(me, position), broadcast_to_map(cast), nearby, asteroids, time_delta)
+        );
+
+        match result {
+            Ok(directives) => directives.into_iter()
+                .filter_map(|d| d.try_cast::<Directive>())
+                .collect(),
+            Err(e) => {
+                // Don't flood the console with the same error every tick
+                println!("Script for ship #{} failed and is disabled: {}", self.id, e);
+                self.script = None;
+                vec![]
+            },
+        }
+    }
+
+    fn debug_info(&self) -> Option<DebugInfo> {
+        if self.script.is_none() {
+            return Some(DebugInfo::new("disabled"));
+        }
+
+        let memory = self.memory.clone().try_cast::<Map>()?;
+
+        Some(DebugInfo {
+            state: memory.get("state").map(|s| s.to_string()).unwrap_or("script".to_string()),
+            perception: Some(PERCEPTION),
+            target: memory.get("target").and_then(map_to_point),
+            path: memory.get("path")
+                .and_then(|p| p.clone().try_cast::<Array>())
+                .map(|p| p.iter().filter_map(map_to_point).collect())
+                .unwrap_or(vec![]),
+            steering: vec![],
+        })
+    }
+}
+
+fn build_engine() -> Engine {
+    let mut engine = Engine::new();
+
+    // Keep scripts from hanging the game or eating all the memory
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(32);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(1024);
+    engine.set_max_array_size(1024);
+    engine.set_max_map_size(256);
+    engine.disable_symbol("eval");
+
+    engine.register_type_with_name::<Directive>("Directive");
+    engine.register_fn("thrust", |m: f64| Directive::Thrust(m));
+    engine.register_fn("rotate", |d: f64| Directive::Rotate(d));
+    engine.register_fn("set_direction", |d: f64| Directive::SetDirection(d));
+    engine.register_fn("aim", |x: f64, y: f64| Directive::Aim(Point::new(x, y)));
+    engine.register_fn("brake", || Directive::Brake);
+
+    engine
+}
+
+fn point_to_map(p: Point) -> Map {
+    let mut map = Map::new();
+    map.insert("x".into(), p.x.into());
+    map.insert("y".into(), p.y.into());
+    map
+}
+
+fn map_to_point(value: &Dynamic) -> Option<Point> {
+    let map = value.clone().try_cast::<Map>()?;
+
+    Some(Point::new(
+        map.get("x")?.as_float().ok()?,
+        map.get("y")?.as_float().ok()?
+    ))
+}
+
+fn ship_to_map(ship: &ShipCache, position: Point) -> Map {
+    let mut map = Map::new();
+    map.insert("id".into(), (ship.id as i64).into());
+    map.insert("category".into(), (ship.category as i64).into());
+    map.insert("x".into(), position.x.into());
+    map.insert("y".into(), position.y.into());
+    map.insert("r".into(), ship.circle.r.into());
+    map.insert("direction".into(), ship.direction.into());
+    map.insert("heading".into(), ship.vector.direction.into());
+    map.insert("speed".into(), ship.vector.magnitude.into());
+    map.insert("health".into(), ship.health.into());
+    map.insert("force".into(), ship.force.into());
+    map.insert("mass".into(), ship.mass.into());
+    map
+}
+
+fn circle_to_map(circle: &Circle) -> Map {
+    let mut map = Map::new();
+    map.insert("x".into(), circle.x.into());
+    map.insert("y".into(), circle.y.into());
+    map.insert("r".into(), circle.r.into());
+    map
+}
+
+fn broadcast_to_map(cast: &Broadcast) -> Map {
+    let mut map = Map::new();
+    map.insert("tick".into(), (cast.tick as i64).into());
+    map.insert("cursor".into(), point_to_map(cast.cursor).into());
+    map.insert("player_id".into(), match cast.player_id {
+        Some(id) => (id as i64).into(),
+        None => Dynamic::UNIT,
+    });
+    map.insert("player_position".into(), point_to_map(cast.player_position).into());
+    map.insert("world".into(), match &cast.world {
+        Some(world) => world_to_map(world).into(),
+        None => Dynamic::UNIT,
+    });
+    map
+}
+
+fn world_to_map(world: &World) -> Map {
+    let mut map = Map::new();
+    map.insert("width".into(), world.get_width().into());
+    map.insert("height".into(), world.get_height().into());
+    map.insert("edge".into(), format!("{:?}", world.edge).to_lowercase().into());
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::ai::*;
+    use crate::ai::script::{Script,ScriptBrain};
+    use crate::broadcast::*;
+    use crate::ship::*;
+
+    fn think(source: &str) -> Vec<Directive> {
+        let ship = ShipBuilder::new(JALAPENO).place(10.0, 20.0).tag(2).build();
+        let mut actors = HashMap::new();
+        actors.insert(2, ship.get_cache(1.0/60.0));
+
+        ScriptBrain::new(2, Script::compile(source).unwrap()).think(1.0/60.0, &Broadcast::new(), &actors, &vec![])
+    }
+
+    #[test]
+    fn test_directives() {
+        let directives = think("fn think(me, cast, actors, asteroids, dt) { [aim(me.x, me.y + 1.0), thrust(0.5)] }");
+
+        assert_eq!(directives, vec![
+            Directive::Aim(crate::physics::Point::new(10.0, 21.0)),
+            Directive::Thrust(0.5),
+        ]);
+    }
+
+    #[test]
+    fn test_broken_script() {
+        assert_eq!(think("fn think(me, cast, actors, asteroids, dt) { loop {} }"), vec![]);
+        assert!(Script::compile("this is not rhai").is_err());
+    }
+}
@@ -10,6 +10,8 @@ use std::f64::consts::{PI,FRAC_PI_2,TAU};
 pub struct Asteroid {
     circle: Circle,
     elasticity: f64,
+    // Name of the gate this is part of, if any
+    group: Option<String>,
 }
 
 impl Asteroid {
@@ -17,6 +19,7 @@ impl Asteroid {
         Asteroid {
             circle: Circle::new(x, y, r),
             elasticity: 1.0,
+            group: None,
         }
     }
 
@@ -60,6 +63,14 @@ impl Asteroid {
         self.elasticity
     }
 
+    pub fn set_group(&mut self, group: &str) {
+        self.group = Some(group.to_string());
+    }
+
+    pub fn in_group(&self, group: &str) -> bool {
+        self.group.as_ref().map(|g| g == group).unwrap_or(false)
+    }
+
     pub fn render_piston(&self) -> [f64; 3] {
         [self.circle.get_x(), self.circle.get_y(), self.circle.get_r()]
     }
@@ -1,15 +1,28 @@
 use std::collections::HashMap;
 use std::cell::RefCell;
 
-use crate::physics::{Point,Vector};
+use crate::difficulty::Difficulty;
+use crate::input::InputState;
+use crate::physics::{Circle,Point,Segment,Vector};
+use crate::scoring::ScoreEvent;
 use crate::ship::*;
+use crate::world::World;
 
 pub struct Broadcast {
     pub tick: u64,
     pub cursor: Point,
-    pub input: Vec<char>,
+    // What each player did with their controls this tick
+    pub inputs: HashMap<u32, InputState>,
+    // Player one, or the first one still alive
     pub player_id: Option<u32>,
     pub player_position: Point,
+    // Every living player, in order
+    pub players: Vec<(u32, Point)>,
+    pub difficulty: Difficulty,
+    // Where the level ends, if it does
+    pub world: Option<World>,
+    // Brains only explain themselves while the debug overlay is on
+    pub debug: bool,
     pub messages: Vec<Message>,
     outbox: RefCell<Vec<Message>>,
 }
@@ -19,9 +32,13 @@ impl Broadcast {
         Broadcast {
             tick: 0,
             cursor: Point::new(0.0, 0.0),
-            input: Vec::new(),
+            inputs: HashMap::new(),
             player_id: None,
             player_position: Point::new(0.0, 0.0),
+            players: Vec::new(),
+            difficulty: Difficulty::Normal,
+            world: None,
+            debug: false,
             outbox: RefCell::new(Vec::new()),
             messages: Vec::new(),
         }
@@ -32,8 +49,16 @@ impl Broadcast {
         self.messages = self.outbox.replace(Vec::new());
     }
 
-    pub fn record_actors(&mut self, actors: &HashMap<u32, ShipCache>, player_id: Option<u32>) {
-        match player_id {
+    pub fn record_actors(&mut self, actors: &HashMap<u32, ShipCache>, player_ids: &[u32]) {
+        self.players = player_ids.iter()
+            .filter(|id| actors[id].health > 0.0)
+            .map(|id| (*id, actors[id].get_point()))
+            .collect();
+
+        // Dead players stay the focus when nobody is left, as with a single player
+        let first = self.players.first().map(|p| p.0).or(player_ids.first().cloned());
+
+        match first {
             Some(id) => self.record_player(&actors[&id]),
             None => println!("No player present. :("),
         }
@@ -44,16 +69,68 @@ impl Broadcast {
         self.player_position = Point::new(player.circle.get_x(), player.circle.get_y());
     }
 
-    pub fn set_pressed(&mut self, pressed: &Vec<char>) {
-        self.input = pressed.clone();
+    pub fn set_input(&mut self, id: u32, input: InputState) {
+        self.inputs.insert(id, input);
     }
 
     pub fn move_cursor(&mut self, cursor: Point) {
         self.cursor = cursor;
     }
 
-    pub fn get_input(&self) -> Vec<char> {
-        self.input.to_vec()
+    pub fn get_input(&self, id: u32) -> InputState {
+        self.inputs.get(&id).cloned().unwrap_or(InputState::new())
+    }
+
+    pub fn is_player(&self, id: u32) -> bool {
+        self.player_id == Some(id) || self.players.iter().any(|p| p.0 == id)
+    }
+
+    // The living player closest to a point, for mobs to go after, as seen
+    // from there, see nearest
+    pub fn nearest_player(&self, point: Point) -> Option<(u32, Point)> {
+        self.players.iter()
+            .map(|p| (p.0, self.nearest(point, p.1)))
+            .min_by(|a, b| point.distance(a.1).partial_cmp(&point.distance(b.1)).unwrap())
+    }
+
+    // Where something is as seen from a point. In a wrapping world that may
+    // be across an edge, anywhere else it is just where it is.
+    pub fn nearest(&self, from: Point, to: Point) -> Point {
+        match &self.world {
+            Some(world) => world.nearest(from, to),
+            None => to,
+        }
+    }
+
+    pub fn nearest_circle(&self, from: Point, circle: Circle) -> Circle {
+        match &self.world {
+            Some(world) => world.nearest_circle(from, circle),
+            None => circle,
+        }
+    }
+
+    pub fn distance(&self, from: Point, to: Point) -> f64 {
+        from.distance(self.nearest(from, to))
+    }
+
+    pub fn sight_line(&self, from: Point, to: Point) -> Segment {
+        Segment::new(from, self.nearest(from, to))
+    }
+
+    // Whether a circle is in the way of a sight line
+    pub fn blocks(&self, line: &Segment, circle: &Circle) -> bool {
+        match &self.world {
+            Some(world) => world.blocks(line, circle),
+            None => line.check_collision_circle(circle),
+        }
+    }
+
+    // Back inside a wrapping world
+    pub fn wrap(&self, point: Point) -> Point {
+        match &self.world {
+            Some(world) => world.wrap(point),
+            None => point,
+        }
     }
 
     pub fn send_message(&self, msg: Message) {
@@ -81,7 +158,9 @@ impl Message {
 #[derive(Clone,Debug)]
 pub enum MessageBody {
     Birth(ShipBuilder),
-    Death,
+    Death(Attribution),
     ShipCollision(u32, Point),
     AsteroidCollision(Point, Point),
+    // Sent by the game, for the HUD
+    Score(ScoreEvent),
 }
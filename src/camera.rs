@@ -1,97 +1,405 @@
-use crate::physics::{Point,Rectangle,Shape};
+use crate::physics::{Point,Rectangle};
 
-const RATIO_X: f64 = 0.5;
-const RATIO_Y: f64 = 0.5;
-const VIEWPORT_X: f64 = (1.0 - RATIO_X) / 2.0;
-const VIEWPORT_Y: f64 = (1.0 - RATIO_Y) / 2.0;
+// Manual zoom, stepped through with ZoomIn and ZoomOut
+const ZOOM_LEVELS: [f64; 6] = [0.5, 0.75, 1.0, 1.25, 1.5, 2.0];
+// At this speed auto-zoom shows twice as much of the world, if it may
+const AUTO_ZOOM_SPEED: f64 = 600.0;
+const AUTO_ZOOM_MIN: f64 = 0.6;
+// Roughly how long, in seconds, it takes to catch up
+const SMOOTH_TIME: f64 = 0.3;
+const ZOOM_SMOOTH_TIME: f64 = 0.8;
+// How many seconds of travel to look ahead, up to a share of the screen
+const LOOK_AHEAD: f64 = 0.4;
+const LOOK_AHEAD_MAX: f64 = 0.25;
+// Room kept around the frame, in units of the world
+const FRAME_MARGIN: f64 = 150.0;
+// Farthest the screen shakes, in units of the world, and how fast it
+// calms down, in trauma per second
+const SHAKE_DISTANCE: f64 = 12.0;
+const SHAKE_DECAY: f64 = 1.5;
+const SHAKE_FREQUENCY: f64 = 40.0;
+
+// Where the world ends up on screen, in the pixels the screen draws in.
+// Screens draw through it and cursors come back through it.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct View {
+    // The point of the world in the top left corner
+    pub offset: Point,
+    // Logical pixels per unit of the world
+    pub zoom: f64,
+    // Screen pixels per logical pixel, e.g. devicePixelRatio
+    pub dpi: f64,
+}
+
+impl View {
+    pub fn new(offset: Point, zoom: f64, dpi: f64) -> View {
+        View {
+            offset: offset,
+            zoom: zoom,
+            dpi: dpi,
+        }
+    }
+
+    // Screen pixels per unit of the world
+    pub fn get_scale(&self) -> f64 {
+        self.zoom * self.dpi
+    }
+
+    pub fn to_screen(&self, point: Point) -> Point {
+        let s = self.get_scale();
+        Point::new((point.x - self.offset.x) * s, (point.y - self.offset.y) * s)
+    }
+
+    pub fn to_world(&self, point: Point) -> Point {
+        let s = self.get_scale();
+        Point::new(point.x / s + self.offset.x, point.y / s + self.offset.y)
+    }
+
+    // The part of the world a screen of this many pixels shows
+    pub fn get_visible(&self, width: f64, height: f64) -> Rectangle {
+        let s = self.get_scale();
+        Rectangle::new(self.offset.x, self.offset.y, width / s, height / s)
+    }
+}
 
 #[derive(Clone,Debug)]
 pub struct Camera {
-    offset: Point,
-    screen: Rectangle,
-    viewport: Rectangle,
-    scale: f64,
+    // The point of the world in the middle of the screen
+    center: Point,
+    velocity: Point,
+    // Where it is headed, look-ahead included
+    target: Point,
+    // Not smoothed on the way to the first target
+    settled: bool,
+    // The part of the world levels are made to show, fitted to the screen
+    base: Point,
+    // Never shows past these, if the level has them
+    bounds: Option<Rectangle>,
+    // Has to stay in view, e.g. every player
+    frame: Option<Rectangle>,
+    // Of the screen, in its own pixels
+    size: Point,
+    fit: f64,
+    dpi: f64,
+    // Relative to fitting the base, both the smoothed zoom and what it is
+    // headed for
+    zoom: f64,
+    zoom_velocity: f64,
+    level: usize,
+    auto_zoom: bool,
+    speed: f64,
+    // From 0 to 1, the screen shakes by its square
+    trauma: f64,
+    time: f64,
     follow: (bool, bool),
 }
 
 impl Camera {
-    pub fn new(width: f64, height: f64, scale: f64, follow: (bool, bool)) -> Camera {
-        let mut camera = Camera {
-            offset: Point::new(0.0, 0.0),
-            screen: Rectangle::new(0.0, 0.0, width, height),
-            viewport: Rectangle::new(0.0, 0.0, 0.0, 0.0),
-            scale: 1.0,
-            follow: follow,
-        };
+    pub fn new(width: f64, height: f64, zoom: f64, follow: (bool, bool)) -> Camera {
+        // The closest level to what was asked for
+        let level = (0..ZOOM_LEVELS.len())
+            .min_by(|&a, &b| (ZOOM_LEVELS[a] - zoom).abs().partial_cmp(&(ZOOM_LEVELS[b] - zoom).abs()).unwrap())
+            .unwrap();
 
-        camera.adjust_viewport();
-
-        camera
+        Camera {
+            center: Point::new(width / 2.0, height / 2.0),
+            velocity: Point::new(0.0, 0.0),
+            target: Point::new(width / 2.0, height / 2.0),
+            settled: false,
+            base: Point::new(width, height),
+            bounds: None,
+            frame: None,
+            size: Point::new(width, height),
+            fit: 1.0,
+            dpi: 1.0,
+            zoom: ZOOM_LEVELS[level],
+            zoom_velocity: 0.0,
+            level: level,
+            auto_zoom: true,
+            speed: 0.0,
+            trauma: 0.0,
+            time: 0.0,
+            follow: follow,
+        }
     }
 
     pub fn get_x(&self) -> f64 {
-        self.offset.x
+        self.get_offset().x
     }
 
     pub fn get_y(&self) -> f64 {
-        self.offset.y
+        self.get_offset().y
     }
-    
+
     pub fn get_offset(&self) -> Point {
-        self.offset
+        self.get_view().offset
+    }
+
+    pub fn get_view(&self) -> View {
+        let zoom = self.fit * self.zoom;
+        let [w, h] = [self.size.x / (zoom * self.dpi), self.size.y / (zoom * self.dpi)];
+        let center = self.clamp(self.center, w, h);
+        let shake = self.get_shake();
+
+        View::new(Point::new(center.x - w / 2.0 + shake.x, center.y - h / 2.0 + shake.y), zoom, self.dpi)
+    }
+
+    pub fn get_center(&self) -> Point {
+        self.center
     }
-    
+
+    // Moves everything the camera is looking at and headed for, which
+    // nobody notices in a world that wraps around
+    pub fn shift(&mut self, by: Point) {
+        self.center += by;
+        self.target += by;
+    }
+
+    pub fn set_bounds(&mut self, bounds: Option<Rectangle>) {
+        self.bounds = bounds;
+    }
+
+    // The part of the world in sight
+    pub fn get_visible(&self) -> Rectangle {
+        self.get_view().get_visible(self.size.x, self.size.y)
+    }
+
     pub fn get_status(&self) -> (bool, bool) {
         self.follow
     }
 
-    pub fn follow(&mut self, x: f64, y: f64) {
-        if self.viewport.check_collision_point(&Point::new(x, y)) {
-            return ();
-        }
+    pub fn is_following(&self) -> bool {
+        self.follow.0 || self.follow.1
+    }
+
+    // Keep a point in frame, looking ahead of where it is going
+    pub fn follow(&mut self, point: Point, velocity: Point) {
+        let visible = self.get_visible();
+        let ahead = Point::new(
+            (velocity.x * LOOK_AHEAD).max(-visible.width * LOOK_AHEAD_MAX).min(visible.width * LOOK_AHEAD_MAX),
+            (velocity.y * LOOK_AHEAD).max(-visible.height * LOOK_AHEAD_MAX).min(visible.height * LOOK_AHEAD_MAX)
+        );
 
         if self.follow.0 {
-            self.follow_x(x);
+            self.target.x = point.x + ahead.x;
         }
 
         if self.follow.1 {
-            self.follow_y(y);
+            self.target.y = point.y + ahead.y;
+        }
+
+        self.speed = (velocity.x * velocity.x + velocity.y * velocity.y).sqrt();
+
+        if !self.settled {
+            self.center = self.target;
+            self.settled = true;
         }
+    }
+
+    // Zooms out as far as it takes to keep this in view while following
+    pub fn frame(&mut self, frame: Option<Rectangle>) {
+        self.frame = frame;
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        self.time += dt;
+        self.trauma = (self.trauma - SHAKE_DECAY * dt).max(0.0);
+
+        self.center.x = smooth_damp(self.center.x, self.target.x, &mut self.velocity.x, SMOOTH_TIME, dt);
+        self.center.y = smooth_damp(self.center.y, self.target.y, &mut self.velocity.y, SMOOTH_TIME, dt);
+
+        let target = self.get_target_zoom();
+        self.zoom = smooth_damp(self.zoom, target, &mut self.zoom_velocity, ZOOM_SMOOTH_TIME, dt);
+    }
+
+    // From 0 to 1, adding up to at most 1
+    pub fn shake(&mut self, trauma: f64) {
+        self.trauma = (self.trauma + trauma).min(1.0);
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.level = (self.level + 1).min(ZOOM_LEVELS.len() - 1);
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.level = self.level.saturating_sub(1);
+    }
 
-        self.adjust_viewport();
+    // Only ever zooms out, and only while following
+    pub fn set_auto_zoom(&mut self, on: bool) {
+        self.auto_zoom = on;
     }
 
-    pub fn follow_x(&mut self, x: f64) {
-        if self.viewport.left() > x || self.viewport.right() < x {
-            let dx = f64::min((self.viewport.left() - x).abs(), (x - self.viewport.right()).abs());
-            let vx = f64::powf(dx / (VIEWPORT_X * self.screen.width), 2.0) * VIEWPORT_X * self.screen.width;
+    // Width and height in screen pixels, so the level fits whatever the
+    // window or canvas has been resized to
+    pub fn update_screen(&mut self, width: f64, height: f64, dpi: f64) {
+        self.size = Point::new(width, height);
+        self.dpi = dpi;
+        self.fit = f64::min(width / self.base.x, height / self.base.y) / dpi;
+    }
+
+    // Keeps a view this wide and high within the bounds, or centred on
+    // them if it is wider or higher
+    fn clamp(&self, center: Point, width: f64, height: f64) -> Point {
+        let bounds = match self.bounds {
+            Some(bounds) => bounds,
+            None => return center,
+        };
+
+        let clamp = |c: f64, size: f64, start: f64, length: f64| match size < length {
+            true => c.max(start + size / 2.0).min(start + length - size / 2.0),
+            false => start + length / 2.0,
+        };
+
+        Point::new(
+            clamp(center.x, width, bounds.x, bounds.width),
+            clamp(center.y, height, bounds.y, bounds.height)
+        )
+    }
+
+    fn get_target_zoom(&self) -> f64 {
+        let zoom = ZOOM_LEVELS[self.level];
+
+        let zoom = match self.auto_zoom && self.is_following() {
+            true => zoom * (1.0 / (1.0 + self.speed / AUTO_ZOOM_SPEED)).max(AUTO_ZOOM_MIN),
+            false => zoom,
+        };
 
-            self.offset.x += vx * f64::signum(x - self.viewport.left());
+        match self.frame {
+            Some(frame) => zoom.min(self.get_frame_zoom(frame)),
+            None => zoom,
         }
     }
 
-    pub fn follow_y(&mut self, y: f64) {
-        if self.viewport.top() > y || self.viewport.bottom() < y {
-            let dy = f64::min((self.viewport.top() - y).abs(), (y - self.viewport.bottom()).abs());
-            let vy = f64::powf(dy / (VIEWPORT_Y * self.screen.height), 2.0) * VIEWPORT_Y * self.screen.height;
-    
-            self.offset.y += vy * f64::signum(y - self.viewport.top());
+    // The closest zoom that still shows the frame around where the camera
+    // is headed, on the axes it follows
+    fn get_frame_zoom(&self, frame: Rectangle) -> f64 {
+        let fit = |follow: bool, target: f64, start: f64, length: f64, size: f64| match follow {
+            true => size / (2.0 * ((target - start).max(start + length - target) + FRAME_MARGIN)),
+            false => f64::INFINITY,
+        };
+
+        let zoom = f64::min(
+            fit(self.follow.0, self.target.x, frame.x, frame.width, self.size.x),
+            fit(self.follow.1, self.target.y, frame.y, frame.height, self.size.y)
+        );
+
+        zoom / (self.fit * self.dpi)
+    }
+
+    // A few out of step waves rather than noise, so it stays smooth
+    fn get_shake(&self) -> Point {
+        let amount = self.trauma * self.trauma * SHAKE_DISTANCE;
+        let t = self.time * SHAKE_FREQUENCY;
+
+        Point::new(
+            amount * ((t).sin() + (t * 2.3 + 1.1).sin()) / 2.0,
+            amount * ((t * 1.7 + 0.5).sin() + (t * 2.9 + 2.3).sin()) / 2.0
+        )
+    }
+}
+
+// A critically damped spring, as fast as it gets without overshooting
+fn smooth_damp(current: f64, target: f64, velocity: &mut f64, smooth_time: f64, dt: f64) -> f64 {
+    let omega = 2.0 / smooth_time;
+    let x = omega * dt;
+    let exp = 1.0 / (1.0 + x + 0.48 * x * x + 0.235 * x * x * x);
+    let change = current - target;
+    let temp = (*velocity + omega * change) * dt;
+
+    *velocity = (*velocity - omega * temp) * exp;
+
+    target + (change + temp) * exp
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::camera::*;
+
+    #[test]
+    fn test_view() {
+        let mut camera = Camera::new(1024.0, 768.0, 1.0, (false, false));
+
+        // Twice the pixels on a high density display, with room to spare
+        camera.update_screen(2400.0, 1536.0, 2.0);
+
+        let view = camera.get_view();
+        assert_eq!(view.get_scale(), 2.0);
+
+        let point = Point::new(300.0, 200.0);
+        let screen = view.to_screen(point);
+        assert_eq!(view.to_world(screen), point);
+        assert_eq!(screen.x, (300.0 - camera.get_x()) * 2.0);
+
+        // Still centred on the level
+        let visible = view.get_visible(2400.0, 1536.0);
+        assert_eq!(visible.width, 1200.0);
+        assert_eq!(visible.height, 768.0);
+        assert_eq!(visible.x, -88.0);
+    }
+
+    #[test]
+    fn test_follow() {
+        let mut camera = Camera::new(1024.0, 768.0, 1.0, (true, true));
+        camera.set_auto_zoom(false);
+
+        // Jumps to the first target, then catches up without overshooting
+        camera.follow(Point::new(0.0, 0.0), Point::new(0.0, 0.0));
+        camera.follow(Point::new(1000.0, 0.0), Point::new(100.0, 0.0));
+
+        let mut last = 0.0;
+        for _ in 0..300 {
+            camera.update(1.0 / 60.0);
+            let visible = camera.get_view().get_visible(1024.0, 768.0);
+            let x = visible.x + visible.width / 2.0;
+
+            assert!(x >= last && x <= 1040.0);
+            last = x;
         }
+
+        // Looking ahead
+        assert!((last - 1040.0).abs() < 1.0);
+
+        camera.zoom_in();
+        camera.shake(1.0);
+
+        for _ in 0..240 {
+            camera.update(1.0 / 60.0);
+        }
+
+        // Calmed down again
+        let visible = camera.get_view().get_visible(1024.0, 768.0);
+        assert!((camera.get_view().zoom - 1.25).abs() < 0.01);
+        assert_eq!((visible.x + visible.width / 2.0).round(), 1040.0);
     }
 
-    pub fn update_screen(&mut self, width: f64, height: f64, scale: f64) {
-        self.screen = Rectangle::new(self.offset.x, self.offset.y, width, height);
-        self.scale = scale;
+    #[test]
+    fn test_bounds() {
+        let mut camera = Camera::new(1024.0, 768.0, 1.0, (true, true));
+        camera.set_auto_zoom(false);
+        camera.set_bounds(Some(Rectangle::new(0.0, 0.0, 2048.0, 768.0)));
+
+        camera.follow(Point::new(2000.0, 700.0), Point::new(0.0, 0.0));
 
-        self.adjust_viewport();
+        let visible = camera.get_visible();
+        assert_eq!((visible.x, visible.y), (1024.0, 0.0));
     }
 
-    pub fn adjust_viewport(&mut self) {
-        self.viewport = Rectangle::new(
-            self.offset.x + (1.0 - RATIO_X) / 2.0 * self.screen.width,
-            self.offset.y + (1.0 - RATIO_Y) / 2.0 * self.screen.height,
-            self.screen.width * RATIO_X,
-            self.screen.height * RATIO_Y
-        );
+    #[test]
+    fn test_frame() {
+        let mut camera = Camera::new(1024.0, 768.0, 1.0, (true, true));
+
+        // Two players far apart, both still in view once it has zoomed out
+        camera.follow(Point::new(1000.0, 0.0), Point::new(0.0, 0.0));
+        camera.frame(Some(Rectangle::new(0.0, -100.0, 2000.0, 200.0)));
+
+        for _ in 0..600 {
+            camera.update(1.0 / 60.0);
+        }
+
+        let visible = camera.get_visible();
+        assert!(visible.x <= 0.0 && visible.x + visible.width >= 2000.0);
+        assert!(visible.y <= -100.0 && visible.y + visible.height >= 100.0);
+        assert!(visible.x + visible.width < 2400.0);
     }
-}
\ No newline at end of file
+}
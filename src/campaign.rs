@@ -0,0 +1,138 @@
+use std::collections::{HashMap,HashSet};
+
+use serde::{Serialize,Deserialize};
+
+use crate::game::Game;
+use crate::storage::*;
+
+const SAVE_KEY: &str = "campaign";
+
+// What is kept between runs
+#[derive(Debug,Default,Serialize,Deserialize)]
+#[serde(default)]
+pub struct Progress {
+    pub unlocked: HashSet<String>,
+    // Best score of every level that has been won
+    pub best: HashMap<String, u32>,
+}
+
+pub struct Campaign {
+    args: CampaignArgs,
+    progress: Progress,
+    store: Box<dyn Store>,
+}
+
+impl Campaign {
+    pub fn new(args: CampaignArgs, store: Box<dyn Store>) -> Campaign {
+        let mut progress: Progress = store.load(SAVE_KEY)
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+
+        progress.unlocked.insert(args.start.to_string());
+
+        Campaign {
+            args: args,
+            progress: progress,
+            store: store,
+        }
+    }
+
+    pub fn from_json(s: &str, store: Box<dyn Store>) -> serde_json::Result<Campaign> {
+        Ok(Campaign::new(serde_json::from_str(s)?, store))
+    }
+
+    pub fn get_start(&self) -> String {
+        self.args.start.to_string()
+    }
+
+    pub fn is_unlocked(&self, level: &str) -> bool {
+        self.progress.unlocked.contains(level)
+    }
+
+    pub fn get_best(&self, level: &str) -> Option<u32> {
+        self.progress.best.get(level).cloned()
+    }
+
+    // Levels of the campaign, with whether they are unlocked and their best score
+    pub fn get_levels(&self) -> Vec<(String, bool, Option<u32>)> {
+        let mut levels: Vec<&String> = self.args.levels.keys().collect();
+        levels.sort();
+
+        levels.iter()
+            .map(|l| (l.to_string(), self.is_unlocked(l), self.get_best(l)))
+            .collect()
+    }
+
+    // Record how the level went and save it. Returns the level to play next,
+    // or None if the campaign is over.
+    pub fn finish(&mut self, level: &str, game: &Game) -> Option<String> {
+        // Levels outside the campaign chain on like they used to
+        let entry = match self.args.levels.get(level) {
+            Some(entry) => entry.clone(),
+            None => return match game.get_victory() {
+                Some(true) => Some(game.get_next()),
+                _ => Some(level.to_string()),
+            },
+        };
+
+        if game.get_victory() != Some(true) {
+            return Some(match &entry.retry {
+                RetryArgs::Level => level.to_string(),
+                RetryArgs::Start => self.get_start(),
+                RetryArgs::Goto(name) => name.to_string(),
+            });
+        }
+
+        let best = self.progress.best.entry(level.to_string()).or_insert(0);
+        *best = (*best).max(game.get_score());
+
+        for unlock in entry.next.iter().chain(entry.unlocks.iter()) {
+            self.progress.unlocked.insert(unlock.to_string());
+        }
+
+        self.save();
+
+        entry.next
+    }
+
+    fn save(&mut self) {
+        match serde_json::to_string(&self.progress) {
+            Ok(s) => self.store.save(SAVE_KEY, &s),
+            Err(e) => println!("Could not save the campaign: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::campaign::*;
+    use crate::game::Game;
+    use crate::physics::Point;
+    use crate::storage::*;
+
+    #[test]
+    fn test_progress() {
+        let mut campaign = Campaign::from_json(r#"{
+            "start": "one",
+            "levels": {
+                "one": {"next": "two", "unlocks": ["bonus"]},
+                "two": {"retry": "start"},
+                "bonus": {}
+            }
+        }"#, Box::new(MemoryStore::default())).unwrap();
+
+        // No mobs and an objective that is met right away
+        let mut won = Game::from_json(r#"{"player": [0, 0.0, 0.0], "objectives": [{"score": 0}]}"#.to_string()).unwrap();
+        won.update(&[], Point::new(0.0, 0.0), 1.0/60.0);
+
+        let lost = Game::from_json(r#"{"player": [0, 0.0, 0.0]}"#.to_string()).unwrap();
+
+        assert!(!campaign.is_unlocked("two"));
+        assert_eq!(campaign.finish("one", &won), Some("two".to_string()));
+        assert!(campaign.is_unlocked("two") && campaign.is_unlocked("bonus"));
+        assert_eq!(campaign.get_best("one"), Some(0));
+        assert_eq!(campaign.finish("two", &lost), Some("one".to_string()));
+        assert_eq!(campaign.finish("bonus", &lost), Some("bonus".to_string()));
+        assert_eq!(campaign.finish("two", &won), None);
+    }
+}
@@ -0,0 +1,73 @@
+use std::str::FromStr;
+
+use serde::{Serialize,Deserialize};
+
+// Normal is the default and the others scale it from there. On Normal, mobs
+// take a moment to react and only see so far, which Hard takes away.
+#[derive(Clone,Copy,Debug,Default,Serialize,Deserialize,PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Difficulty, String> {
+        match s.to_lowercase().as_str() {
+            "easy" => Ok(Difficulty::Easy),
+            "normal" => Ok(Difficulty::Normal),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(format!("Unknown difficulty: {}", s)),
+        }
+    }
+}
+
+impl Difficulty {
+    // Ticks a mob needs to react after spotting the player
+    pub fn reaction_delay(&self) -> u64 {
+        match self {
+            Difficulty::Easy => 45,
+            Difficulty::Normal => 15,
+            Difficulty::Hard => 0,
+        }
+    }
+
+    // How far away mobs can spot the player
+    pub fn perception(&self) -> f64 {
+        match self {
+            Difficulty::Easy => 600.0,
+            Difficulty::Normal => 1200.0,
+            Difficulty::Hard => f64::INFINITY,
+        }
+    }
+
+    // Multiplies the thrust of mobs
+    pub fn force(&self) -> f64 {
+        match self {
+            Difficulty::Easy => 0.75,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 1.3,
+        }
+    }
+
+    // Multiplies the delays and intervals of spawner waves
+    pub fn spawn_delay(&self) -> f64 {
+        match self {
+            Difficulty::Easy => 1.5,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 2.0 / 3.0,
+        }
+    }
+
+    pub fn score(&self) -> f64 {
+        match self {
+            Difficulty::Easy => 0.5,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 2.0,
+        }
+    }
+}
@@ -1,22 +1,103 @@
-use std::collections::HashMap;
+use std::collections::{HashMap,HashSet};
+use std::str::FromStr;
 use serde::{Serialize,Deserialize};
+use serde::de::Error;
 use serde_json::{Result, Value, Deserializer};
+use rand::{Rng, SeedableRng, rngs::StdRng};
 
+use crate::ai::{Brain,BrainArgs};
+use crate::ai::encoding;
 use crate::asteroid::*;
 use crate::broadcast::*;
 use crate::camera::*;
-use crate::physics::Point;
+use crate::difficulty::Difficulty;
+use crate::input::{Action,Inputs,InputState};
+use crate::minimap::Radar;
+use crate::objective::*;
+use crate::physics::{Point,Rectangle,Shape};
 use crate::ship::*;
 use crate::storage::*;
+use crate::scoring::*;
 use crate::spawner::*;
+use crate::statistics::Statistics;
+use crate::trigger::*;
+use crate::widget::Widget;
+use crate::world::*;
 
-const UPS: u64 = 60;
+pub const UPS: u64 = 60;
+// How long a HUD message stays up, in ticks
+const NOTICE_TICKS: u64 = 240;
+pub const MAX_PLAYERS: usize = 4;
+// Points for taking out another player in versus
+const VERSUS_POINTS: u32 = 500;
+// Between players placed next to player one, when the level has no spots for them
+const PLAYER_SPACING: f64 = 60.0;
+// How much collisions shake the screen, see Camera::shake
+const SHAKE_SHIP: f64 = 0.5;
+const SHAKE_ASTEROID: f64 = 0.35;
+const SHAKE_NEARBY: f64 = 0.1;
+// How far past an open edge ships stop being drawn
+const LOST_DISTANCE: f64 = 500.0;
+
+// How local players get along
+#[derive(Clone,Copy,Debug,Default,Deserialize,PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    // Against the mobs, the level is lost once everyone is dead
+    #[default]
+    Coop,
+    // Against each other, the last one alive wins
+    Versus,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Mode, String> {
+        match s.to_lowercase().as_str() {
+            "coop" | "co-op" => Ok(Mode::Coop),
+            "versus" | "vs" => Ok(Mode::Versus),
+            _ => Err(format!("Unknown mode: {}", s)),
+        }
+    }
+}
+
+// A local player's ship, and what they have earned with it
+struct Player {
+    ship: Ship,
+    score: u32,
+    scoring: Scoring,
+}
+
+impl Player {
+    fn new(ship: Ship) -> Player {
+        Player {
+            ship: ship,
+            score: 0,
+            scoring: Scoring::new(),
+        }
+    }
+
+    fn is_alive(&self) -> bool {
+        self.ship.get_health() > 0.0
+    }
+
+    // Share of health left, from 0 to 1
+    fn get_health(&self) -> f64 {
+        self.ship.get_health().max(0.0) / HEALTH[self.ship.get_category()]
+    }
+}
 
 pub struct Game {
     tick: u64,
-    player: Ship,
+    // Player one first
+    players: Vec<Player>,
+    // Where players after the first start out
+    spawns: Vec<ShipArgs>,
+    mode: Mode,
+    // Team total
     score: u32,
-    spawner: ShipSpawner,
+    spawner: WaveSpawner,
     mobs: Vec<Ship>,
     asteroids: Vec<Asteroid>,
     ship_count: u32,
@@ -25,49 +106,105 @@ pub struct Game {
     next: String,
     camera: Camera,
     broadcast: Broadcast,
-    pressed: Vec<char>,
+    rng: StdRng,
+    brains: HashMap<String, BrainArgs>,
+    classes: HashMap<usize, BrainArgs>,
+    triggers: Triggers,
+    // Replaces killing every mob as the way to win, if the level has any
+    objective: Option<Objective>,
+    allies: HashSet<u32>,
+    // Level file tags of ships, and tags with no ship left alive
+    tags: HashMap<u32, String>,
+    destroyed: HashSet<String>,
+    // Walls of opened gates, kept around in case they close again
+    gates: HashMap<String, Vec<Asteroid>>,
+    // HUD messages and the tick they go away
+    notices: Vec<(String, u64)>,
+    stats: Statistics,
 }
 
 impl Game {
-    pub fn new(
-        player: ShipArgs,
-        mobs: Vec<ShipArgs>,
-        asteroids: Vec<AsteroidArgs>,
-        walls: Vec<WallArgs>,
-        spawner: bool,
-        next: String,
-        camera_follow: (bool, bool),
-    ) -> Game {
+    pub fn new(level: LevelArgs) -> Game {
         let mut factory = ShipFactory::new();
+        let player = level.player;
+
+        // Levels from before waves only had an endless spawner to turn on
+        let spawner = match level.endless {
+            true => WaveSpawner::endless(),
+            false => WaveSpawner::new(level.waves),
+        };
 
         let mut game = Game {
             tick: 0,
-            player: ShipBuilder::from(&player).tag(1).build(),
+            players: Vec::new(),
+            spawns: Vec::new(),
+            mode: Mode::Coop,
             score: 0,
-            spawner: ShipSpawner::new(spawner),
+            spawner: spawner,
             mobs: Vec::new(),
             asteroids: Vec::new(),
             ship_count: 1,
             cached_actors: HashMap::new(),
             victory: None,
-            next: next,
-            camera: Camera::new(1024.0, 768.0, 1.0, camera_follow),
+            next: level.next,
+            camera: Camera::new(1024.0, 768.0, 1.0, level.camera_follow),
             broadcast: Broadcast::new(),
-            pressed: Vec::new(),
+            rng: StdRng::seed_from_u64(0),
+            brains: level.brains,
+            classes: HashMap::new(),
+            triggers: Triggers::new(level.events),
+            objective: level.objective.as_ref().map(Objective::new),
+            allies: HashSet::new(),
+            tags: HashMap::new(),
+            destroyed: HashSet::new(),
+            gates: HashMap::new(),
+            notices: Vec::new(),
+            stats: Statistics::default(),
         };
 
-        for ship in mobs.iter() {
-            game.create_ship(ShipBuilder::from(ship));
+        game.players.push(Player::new(ShipBuilder::from(&player).tag(1).build()));
+
+        // Without spots in the level, the others line up next to player one
+        game.spawns = match level.spawns.is_empty() {
+            true => (1..MAX_PLAYERS)
+                .map(|i| ShipArgs(player.0, player.1 + PLAYER_SPACING * i as f64, player.2, player.3, player.4, player.5.clone()))
+                .collect(),
+            false => level.spawns,
+        };
+
+        game.set_difficulty(level.difficulty);
+
+        // Brains replacing the default for every ship of a category, including spawned ones
+        for (category, name) in level.classes.iter() {
+            let brain = game.get_brain(name);
+            game.classes.insert(*category, brain);
         }
 
-        for asteroid in asteroids.iter() {
+        for ship in level.mobs.iter() {
+            let builder = game.build_ship(ship);
+            game.create_ship(builder);
+        }
+
+        for asteroid in level.asteroids.iter() {
             game.asteroids.push(Asteroid::from(asteroid))
         }
 
-        for wall in walls.iter() {
+        for wall in level.walls.iter() {
             game.asteroids.append(&mut Asteroid::from_wall_args(wall));
         }
 
+        // Gates start out closed
+        for (name, walls) in level.gates.iter() {
+            for wall in walls.iter() {
+                for mut asteroid in Asteroid::from_wall_args(wall) {
+                    asteroid.set_group(name);
+                    game.asteroids.push(asteroid);
+                }
+            }
+        }
+
+        game.set_world(level.world.as_ref().map(World::from));
+
         game
     }
 
@@ -75,46 +212,158 @@ impl Game {
         let json: Value = serde_json::from_str(&s).unwrap();
 
         let player: ShipArgs = serde_json::from_value(json["player"].clone())?;
+        let spawns: Vec<ShipArgs> = serde_json::from_value(json["players"].clone()).unwrap_or(vec![]);
         let mobs: Vec<ShipArgs> = serde_json::from_value(json["mobs"].clone()).unwrap_or(vec![]);
         let asteroids: Vec<AsteroidArgs> = serde_json::from_value(json["asteroids"].clone()).unwrap_or(vec![]);
         let walls: Vec<WallArgs> = serde_json::from_value(json["walls"].clone()).unwrap_or(vec![]);
-        let spawner = serde_json::from_value(json["spawner"].clone()).unwrap_or(false);
+        let gates: HashMap<String, Vec<WallArgs>> = serde_json::from_value(json["gates"].clone()).unwrap_or(HashMap::new());
+        let events: Vec<EventArgs> = serde_json::from_value(json["events"].clone()).unwrap_or(vec![]);
+        // A list of objectives means all of them
+        let objective = match &json["objectives"] {
+            Value::Array(_) => Some(ObjectiveArgs::All(serde_json::from_value(json["objectives"].clone())?)),
+            Value::Null => None,
+            value => Some(serde_json::from_value(value.clone())?),
+        };
+        // Brains that fail to load fail the level, networks and scripts included
+        let brains: HashMap<String, BrainArgs> = match &json["brains"] {
+            Value::Null => HashMap::new(),
+            value => serde_json::from_value(value.clone())?,
+        };
+        let classes: HashMap<usize, String> = serde_json::from_value(json["classes"].clone()).unwrap_or(HashMap::new());
+        let difficulty = serde_json::from_value(json["difficulty"].clone()).unwrap_or(Difficulty::Normal);
+        let waves: Vec<WaveArgs> = serde_json::from_value(json["waves"].clone()).unwrap_or(vec![]);
+        let endless = serde_json::from_value(json["spawner"].clone()).unwrap_or(false);
         let next = serde_json::from_value(json["next"].clone()).unwrap_or("level1".to_string());
         let camera_follow = serde_json::from_value(json["camera_follow"].clone()).unwrap_or((false, false));
+        let world: Option<WorldArgs> = serde_json::from_value(json["world"].clone()).unwrap_or(None);
 
-        Ok(Game::new(player, mobs, asteroids, walls, spawner, next, camera_follow))
+        let level = LevelArgs {
+            player: player,
+            spawns: spawns,
+            mobs: mobs,
+            asteroids: asteroids,
+            walls: walls,
+            gates: gates,
+            events: events,
+            objective: objective,
+            brains: brains,
+            classes: classes,
+            difficulty: difficulty,
+            waves: waves,
+            endless: endless,
+            next: next,
+            camera_follow: camera_follow,
+            world: world,
+        };
+
+        // As does asking for a brain the level doesn't have
+        if let Some(name) = level.get_brain_names().into_iter().find(|n| !level.brains.contains_key(*n)) {
+            return Err(serde_json::Error::custom(format!("Brain {} not found.", name)));
+        }
+
+        Ok(Game::new(level))
+    }
+
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.spawner.seed(self.rng.gen());
+    }
+
+    pub fn set_difficulty(&mut self, difficulty: Difficulty) {
+        self.broadcast.difficulty = difficulty;
+    }
+
+    pub fn set_player_brain(&mut self, brain: Box<dyn Brain>) {
+        self.players[0].ship.set_brain(brain);
+    }
+
+    // Local players, from one up to MAX_PLAYERS, before the first update
+    pub fn set_players(&mut self, count: usize, mode: Mode) {
+        self.mode = mode;
+
+        while self.players.len() < count.min(MAX_PLAYERS) {
+            let spawn = &self.spawns[(self.players.len() - 1) % self.spawns.len()];
+            let builder = ShipBuilder::from(spawn);
+
+            self.ship_count += 1;
+            self.players.push(Player::new(builder.tag(self.ship_count).build()));
+        }
+
+        self.players.truncate(count.max(1));
     }
 
-    pub fn update(&mut self, pressed: &Vec<char>, cursor: Point, time_delta: f64) -> bool {
+    // Give every mob, present and future, the same kind of brain
+    pub fn set_mob_brains(&mut self, brain: &BrainArgs) {
+        for mob in self.mobs.iter_mut() {
+            mob.set_brain(brain.build(mob.get_id()));
+        }
+
+        for category in [BELL, JALAPENO, CAYENNE, CHICKPEA].iter() {
+            self.classes.insert(*category, brain.clone());
+        }
+    }
+
+    pub fn update(&mut self, pressed: &[Action], cursor: Point, time_delta: f64) -> bool {
+        let mut inputs = Inputs::new();
+        inputs.pressed = pressed.to_vec();
+        inputs.cursor = cursor;
+
+        self.update_players(&[inputs], time_delta)
+    }
+
+    // Like update, with the controls of every player in order, see
+    // Controls::tick
+    pub fn update_players(&mut self, inputs: &[Inputs], time_delta: f64) -> bool {
         self.tick += 1;
 
         self.broadcast.update(self.tick);
-        self.broadcast.set_pressed(pressed);
-        self.broadcast.move_cursor(cursor + self.camera.get_offset());
+
+        for (player, input) in self.players.iter().zip(inputs.iter()) {
+            let id = player.ship.get_id();
+            let last = self.broadcast.get_input(id);
+            let state = InputState::from_inputs(input, &last, self.camera.get_view().to_world(input.cursor));
+
+            self.broadcast.set_input(id, state);
+        }
+
+        // Player one has the mouse
+        let cursor = inputs.first().map(|i| i.cursor).unwrap_or(Point::new(0.0, 0.0));
+
+        self.broadcast.move_cursor(self.camera.get_view().to_world(cursor));
         self.read_messages();
+        self.check_triggers();
+        self.check_objective();
 
-        self.spawner.act(&self.broadcast);
+        let alive = self.count_enemies();
+        self.spawner.act(&self.broadcast, alive);
 
         // Flush cache
         self.cached_actors.clear();
 
-        // Cache player
-        self.cached_actors.insert(self.player.get_id(), self.player.get_cache(time_delta as f64));
+        // Cache players
+        for player in self.players.iter() {
+            self.cached_actors.insert(player.ship.get_id(), player.ship.get_cache(time_delta));
+        }
 
         // Cache non-player characters
         for mob in self.mobs.iter() {
-            self.cached_actors.insert(mob.get_id(), mob.get_cache(time_delta as f64));
+            self.cached_actors.insert(mob.get_id(), mob.get_cache(time_delta));
         }
 
-        self.broadcast.record_actors(&self.cached_actors, Some(self.player.get_id()));
+        let ids = self.get_player_ids();
+        self.broadcast.record_actors(&self.cached_actors, &ids);
 
-        //self.player.add_inputs(self.broadcast.input.to_vec());
-        self.player.act(time_delta as f64, &self.broadcast, &self.cached_actors, &self.asteroids);
+        for player in self.players.iter_mut() {
+            self.stats.record_speed(self.cached_actors[&player.ship.get_id()].vector.magnitude.abs());
+            player.ship.act(time_delta, &self.broadcast, &self.cached_actors, &self.asteroids);
+        }
 
         for mob in self.mobs.iter_mut() {
-            mob.act(time_delta as f64, &self.broadcast, &self.cached_actors, &self.asteroids);
+            mob.act(time_delta, &self.broadcast, &self.cached_actors, &self.asteroids);
         }
 
+        self.update_camera(time_delta);
+
         match self.victory {
             Some(_) => false,
             None => true,
@@ -122,44 +371,279 @@ impl Game {
     }
 
     pub fn render<S: Screen>(&mut self, screen: &mut S) {
-        let status = self.camera.get_status();
-        if status.0 || status.1 {
-            self.camera.follow(self.player.get_x(), self.player.get_y());
-            screen.set_offset(self.camera.get_offset());
-        }
+        screen.set_view(self.camera.get_view());
 
         screen.draw_background();
         
-        for (id, ship) in self.cached_actors.iter() {
-            screen.draw_ship(&ship, 1.0/60.0, self.tick);
+        // Ships lost in the void are no longer worth pointing out
+        let lost = |ship: &ShipCache| match &self.broadcast.world {
+            Some(world) => world.distance_to_edge(ship.get_point()) < -LOST_DISTANCE,
+            None => false,
+        };
+
+        let visible = self.camera.get_visible();
+        let center = Point::new(visible.x + visible.width / 2.0, visible.y + visible.height / 2.0);
+
+        match self.broadcast.world.filter(|w| w.is_wrapping()) {
+            // Everything near an edge shows up by the other one too
+            Some(world) => {
+                for ship in self.cached_actors.values() {
+                    let images = world.images(ship.circle, &visible);
+
+                    // Off screen, the short way round
+                    if images.is_empty() {
+                        screen.draw_ship(&ship.moved_to(world.nearest_circle(center, ship.circle)), 1.0/60.0, self.tick);
+                    }
+
+                    for circle in images {
+                        screen.draw_ship(&ship.moved_to(circle), 1.0/60.0, self.tick);
+                    }
+                }
+
+                for asteroid in self.asteroids.iter() {
+                    for circle in world.images(asteroid.get_circle(), &visible) {
+                        screen.draw_asteroid(&Asteroid::new(circle.x, circle.y, circle.r));
+                    }
+                }
+            },
+            None => {
+                for (id, ship) in self.cached_actors.iter().filter(|(_, ship)| !lost(ship)) {
+                    screen.draw_ship(ship, 1.0/60.0, self.tick);
+                }
+
+                for asteroid in self.asteroids.iter() {
+                    screen.draw_asteroid(asteroid)
+                }
+            },
         }
 
-        for asteroid in self.asteroids.iter() {
-            screen.draw_asteroid(&asteroid)
+        if self.broadcast.debug {
+            for (id, ship) in self.cached_actors.iter() {
+                screen.draw_debug(ship);
+            }
         }
 
         //println!("They hatin'?");
     }
 
+    pub fn toggle_debug(&mut self) {
+        self.broadcast.debug = !self.broadcast.debug;
+    }
+
+    // The screen's size in its own pixels, and how many of those to a
+    // logical pixel
+    pub fn resize(&mut self, width: f64, height: f64, dpi: f64) {
+        self.camera.update_screen(width, height, dpi);
+    }
+
+    pub fn set_world(&mut self, world: Option<World>) {
+        // Wrapping around means there is no edge to stop at
+        self.camera.set_bounds(match world {
+            Some(w) if w.edge != Edge::Wrap => Some(w.bounds),
+            _ => None,
+        });
+        self.broadcast.world = world;
+    }
+
+    pub fn get_world(&self) -> Option<World> {
+        self.broadcast.world
+    }
+
+    // The part of the world on screen
+    pub fn get_visible(&self) -> Rectangle {
+        self.camera.get_visible()
+    }
+
+    pub fn get_view(&self) -> View {
+        self.camera.get_view()
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.camera.zoom_in();
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.camera.zoom_out();
+    }
+
+    // Zoom out the faster the players go
+    pub fn set_auto_zoom(&mut self, on: bool) {
+        self.camera.set_auto_zoom(on);
+    }
+
     pub fn get_broadcast(&self) -> &Broadcast {
         &self.broadcast
     }
 
+    pub fn get_asteroids(&self) -> &Vec<Asteroid> {
+        &self.asteroids
+    }
+
+    // Every ship as of the last update, the dead ones included
+    pub fn get_actors(&self) -> &HashMap<u32, ShipCache> {
+        &self.cached_actors
+    }
+
+    // Best combo going among the players
+    pub fn get_combo(&self) -> u32 {
+        self.players.iter().map(|p| p.scoring.get_combo()).max().unwrap_or(0)
+    }
+
+    pub fn get_tick(&self) -> u64 {
+        self.tick
+    }
+
+    // Health enemies lost with a player at the end of the blame chain, so
+    // not what they did to each other or ran into on their own
+    pub fn get_player_damage(&self) -> f64 {
+        let ids = self.get_player_ids();
+
+        self.mobs.iter()
+            .filter(|m| !self.allies.contains(&m.get_id()))
+            .map(|m| ids.iter().map(|id| m.get_damage_by(*id)).sum::<f64>())
+            .sum()
+    }
+
+    // The other way round: health players lost with an enemy at the end of
+    // the blame chain, so no asteroids or walls
+    pub fn get_mob_damage(&self) -> f64 {
+        let ids: Vec<u32> = self.mobs.iter()
+            .map(|m| m.get_id())
+            .filter(|id| !self.allies.contains(id))
+            .collect();
+
+        self.players.iter()
+            .map(|p| ids.iter().map(|id| p.ship.get_damage_by(*id)).sum::<f64>())
+            .sum()
+    }
+
+    // Statistics of the level so far, for the end-of-level summary
+    pub fn get_statistics(&self) -> Statistics {
+        let mut stats = self.stats.clone();
+
+        stats.damage_dealt = self.get_player_damage();
+        stats.damage_taken = self.players.iter()
+            .map(|p| HEALTH[p.ship.get_category()] - p.ship.get_health().max(0.0))
+            .sum();
+        stats.time = self.tick as f64 / UPS as f64;
+        stats.score = self.score;
+
+        stats
+    }
+
+    pub fn get_victory(&self) -> Option<bool> {
+        self.victory
+    }
+
+    // The player's view of the world, as used for training
+    pub fn observe(&self) -> Vec<f64> {
+        let mut actors = HashMap::new();
+        let player = &self.players[0].ship;
+        actors.insert(player.get_id(), player.get_cache(1.0/60.0));
+
+        for mob in self.mobs.iter() {
+            actors.insert(mob.get_id(), mob.get_cache(1.0/60.0));
+        }
+
+        encoding::observe(player.get_id(), Some(player.get_id()), self.broadcast.world.as_ref(), &actors, &self.asteroids)
+    }
+
     pub fn get_score(&self) -> u32 {
         self.score
     }
 
+    // Player one
+    pub fn get_player_id(&self) -> u32 {
+        self.players[0].ship.get_id()
+    }
+
+    pub fn get_player_ids(&self) -> Vec<u32> {
+        self.players.iter().map(|p| p.ship.get_id()).collect()
+    }
+
+    pub fn is_player(&self, id: u32) -> bool {
+        self.players.iter().any(|p| p.ship.get_id() == id)
+    }
+
     pub fn get_player_health(&self) -> f64 {
-        self.cached_actors[&self.player.get_id()].health
+        self.players[0].ship.get_health()
+    }
+
+    pub fn get_mode(&self) -> Mode {
+        self.mode
+    }
+
+    // Score and health of every player, for the HUD
+    pub fn get_player_status(&self) -> Vec<(u32, f64)> {
+        self.players.iter()
+            .map(|p| (p.score, p.ship.get_health().max(0.0)))
+            .collect()
+    }
+
+    // The last player standing in versus, counting from zero
+    pub fn get_winner(&self) -> Option<usize> {
+        match self.is_versus() && self.victory == Some(true) {
+            true => self.players.iter().position(|p| p.is_alive()),
+            false => None,
+        }
+    }
+
+    // HUD messages currently showing, oldest first
+    pub fn get_notices(&self) -> Vec<String> {
+        self.notices.iter()
+            .filter(|n| n.1 > self.tick)
+            .map(|n| n.0.to_string())
+            .collect()
+    }
+
+    // Objective progress for the HUD, empty if the level has none
+    pub fn get_objectives(&self) -> Vec<String> {
+        match &self.objective {
+            Some(objective) => objective.describe(),
+            None => Vec::new(),
+        }
     }
 
     pub fn get_player_speed(&self) -> f64 {
-        self.cached_actors[&self.player.get_id()].vector.magnitude.abs()
+        self.cached_actors[&self.get_player_id()].vector.magnitude.abs()
     }
 
-    fn create_ship(&mut self, ship: ShipBuilder) {
+    // Unknown names leave the ship with the default brain of its category
+    // Names were checked when the level loaded
+    fn get_brain(&self, name: &str) -> BrainArgs {
+        self.brains[name].clone()
+    }
+
+    fn build_ship(&self, ship: &ShipArgs) -> ShipBuilder {
+        let builder = ShipBuilder::from(ship);
+
+        match &ship.5.brain {
+            Some(name) => builder.brain(self.get_brain(name)),
+            None => builder,
+        }
+    }
+
+    fn create_ship(&mut self, mut ship: ShipBuilder) {
         self.ship_count += 1;
-        
+
+        if let Some(label) = ship.get_label() {
+            self.tags.insert(self.ship_count, label);
+        }
+
+        if ship.is_ally() {
+            self.allies.insert(self.ship_count);
+
+            if !ship.has_brain() {
+                ship = ship.brain(BrainArgs::Escort);
+            }
+        }
+
+        if !ship.has_brain() {
+            if let Some(brain) = self.classes.get(&ship.get_category()) {
+                ship = ship.brain(brain.clone());
+            }
+        }
+
         self.mobs.push(ship.tag(self.ship_count).build());
     }
 
@@ -171,49 +655,385 @@ impl Game {
 
         for msg in messages {
             match msg.body {
-                MessageBody::Death => self.process_death(msg.sender),
+                MessageBody::Death(attribution) => self.process_death(msg.sender, &attribution),
                 MessageBody::Birth(ship) => self.create_ship(ship),
+                MessageBody::ShipCollision(id, p) => self.process_collision(msg.sender, Some(id), p),
+                MessageBody::AsteroidCollision(_, p) => self.process_collision(msg.sender, None, p),
                 _ => ()
             }
         }
+
+        // Only after births, so ships from the last wave count as alive
+        self.check_victory();
     }
 
-    fn process_death(&mut self, id: u32) {
-        if id == self.player.get_id() {
+    fn process_death(&mut self, id: u32, attribution: &Attribution) {
+        if self.is_player(id) {
+            self.process_player_death(id, attribution);
+            return;
+        }
+
+        let ship = &self.cached_actors[&id];
+        let killer = attribution.get_killer()
+            .and_then(|k| self.players.iter().position(|p| p.ship.get_id() == k));
+
+        // Only the players' kills are worth anything
+        if self.allies.contains(&id) {
+            println!("Ally #{} was destroyed.", id);
+        } else if let Some(index) = killer {
+            let player = &mut self.players[index];
+            let health = player.get_health();
+
+            let events = player.scoring.score_kill(
+                ship.category, ship.get_point(), self.tick, attribution, health, self.broadcast.difficulty.score()
+            );
+
+            self.stats.record_kill(ship.category);
+
+            for event in events {
+                self.award(index, event);
+            }
+
+            println!("Ship #{} was killed by player {}. New score: {}", id, index + 1, self.score);
+        } else {
+            self.stats.record_environmental_kill();
+            println!("Ship #{} was destroyed by {:?}.", id, attribution.blow);
+        }
+
+        if let Some(tag) = self.tags.get(&id) {
+            let alive = self.mobs.iter()
+                .filter(|m| m.get_health() > 0.0 && self.tags.get(&m.get_id()) == Some(tag))
+                .count();
+
+            if alive == 0 {
+                self.destroyed.insert(tag.to_string());
+            }
+        }
+    }
+
+    fn process_player_death(&mut self, id: u32, attribution: &Attribution) {
+        let index = self.players.iter().position(|p| p.ship.get_id() == id).unwrap();
+        let alive = self.players.iter().filter(|p| p.is_alive()).count();
+
+        if self.is_versus() {
+            let killer = attribution.get_killer()
+                .and_then(|k| self.players.iter().position(|p| p.ship.get_id() == k));
+
+            if let Some(k) = killer.filter(|k| *k != index) {
+                let points = (VERSUS_POINTS as f64 * self.broadcast.difficulty.score()).round() as u32;
+
+                self.award(k, ScoreEvent {
+                    points: points,
+                    position: self.cached_actors[&id].get_point(),
+                    label: format!("Player {} down!", index + 1),
+                });
+            }
+
+            // Players can go down together, in which case nobody wins
+            if alive <= 1 && self.victory.is_none() {
+                self.victory = Some(alive == 1);
+            }
+        } else if alive == 0 {
             self.victory = Some(false);
+        }
+
+        if self.players.len() > 1 {
+            println!("Player {} is out.", index + 1);
+        }
+    }
+
+    fn award(&mut self, index: usize, event: ScoreEvent) {
+        self.players[index].score += event.points;
+        self.score += event.points;
+        self.broadcast.send_message(Message::new(0, 0, MessageBody::Score(event)));
+    }
+
+    fn is_versus(&self) -> bool {
+        self.mode == Mode::Versus && self.players.len() > 1
+    }
+
+    // First living player, or player one if everyone is dead
+    fn get_focus(&self) -> Point {
+        let ship = &self.players.iter().find(|p| p.is_alive()).unwrap_or(&self.players[0]).ship;
+        Point::new(ship.get_x(), ship.get_y())
+    }
+
+    // A ship ran into another ship, or an asteroid if there is no other
+    fn process_collision(&mut self, sender: u32, other: Option<u32>, point: Point) {
+        if self.is_player(sender) {
+            self.stats.record_collision(other.is_some());
+        }
+
+        // Players feel their own bumps the most, everyone else's only if
+        // they can see them
+        let involved = self.is_player(sender) || other.is_some_and(|id| self.is_player(id));
+        if involved {
+            self.camera.shake(match other {
+                Some(_) => SHAKE_SHIP,
+                None => SHAKE_ASTEROID,
+            });
+        } else if self.camera.get_visible().check_collision_point(&point) {
+            self.camera.shake(SHAKE_NEARBY);
+        }
+    }
+
+    // Frame everyone still playing
+    fn update_camera(&mut self, time_delta: f64) {
+        let alive: Vec<&Player> = self.players.iter().filter(|p| p.is_alive()).collect();
+        let center = self.camera.get_center();
+        let mut frame = None;
+
+        if self.camera.is_following() && !alive.is_empty() {
+            let n = alive.len() as f64;
+            let mut min = Point::new(f64::INFINITY, f64::INFINITY);
+            let mut max = Point::new(f64::NEG_INFINITY, f64::NEG_INFINITY);
+            let mut velocity = Point::new(0.0, 0.0);
+
+            for player in alive.iter() {
+                // Whichever copy is closest, so crossing an edge is no jump
+                let p = self.broadcast.nearest(center, Point::new(player.ship.get_x(), player.ship.get_y()));
+                min = Point::new(min.x.min(p.x), min.y.min(p.y));
+                max = Point::new(max.x.max(p.x), max.y.max(p.y));
+
+                if let Some(ship) = self.cached_actors.get(&player.ship.get_id()) {
+                    velocity += Point::new(ship.vector.get_dx() / n, ship.vector.get_dy() / n);
+                }
+            }
+
+            let bounds = Rectangle::new(min.x, min.y, max.x - min.x, max.y - min.y);
+            self.camera.follow(Point::new(min.x + bounds.width / 2.0, min.y + bounds.height / 2.0), velocity);
+
+            // With company, the camera zooms out to keep everyone in the picture
+            if alive.len() > 1 {
+                frame = Some(bounds);
+            }
+        }
+
+        self.camera.frame(frame);
+
+        self.camera.update(time_delta);
+
+        // Back over the world itself, which looks no different
+        let center = self.camera.get_center();
+        self.camera.shift(self.broadcast.wrap(center) - center);
+    }
+
+    // Living mobs that stand between the player and victory
+    fn count_enemies(&self) -> usize {
+        self.mobs.iter()
+            .filter(|m| m.get_health() > 0.0 && !self.allies.contains(&m.get_id()))
+            .count()
+    }
+
+    fn count_tags(&self) -> HashMap<String, usize> {
+        let mut ret = HashMap::new();
+
+        for mob in self.mobs.iter().filter(|m| m.get_health() > 0.0) {
+            if let Some(tag) = self.tags.get(&mob.get_id()) {
+                *ret.entry(tag.to_string()).or_insert(0) += 1;
+            }
+        }
+
+        ret
+    }
+
+    fn check_triggers(&mut self) {
+        let alive = self.count_tags();
+
+        let actions = self.triggers.check(&Situation {
+            tick: self.tick,
+            score: self.score,
+            player: self.get_focus(),
+            destroyed: &self.destroyed,
+            alive: &alive,
+        });
+
+        for action in actions {
+            match action {
+                ActionArgs::Spawn(ships) => {
+                    for ship in ships.iter() {
+                        let builder = self.build_ship(ship);
+                        self.create_ship(builder);
+                    }
+                },
+                ActionArgs::Open(name) => self.open_gate(&name),
+                ActionArgs::Close(name) => self.close_gate(&name),
+                ActionArgs::Message(text) => {
+                    println!("{}", text);
+                    let tick = self.tick;
+                    self.notices.retain(|n| n.1 > tick);
+                    self.notices.push((text, self.tick + NOTICE_TICKS));
+                },
+                ActionArgs::Victory => if self.victory.is_none() { self.victory = Some(true) },
+                ActionArgs::Defeat => if self.victory.is_none() { self.victory = Some(false) },
+            }
+        }
+    }
+
+    fn check_objective(&mut self) {
+        // Players only have each other to beat in versus
+        if self.is_versus() {
             return;
         }
 
-        self.score += match self.cached_actors[&id].category {
-            1 => 100,
-            2 => 300,
-            _ => 0,
+        let alive = self.count_tags();
+        let focus = self.get_focus();
+
+        let status = match &mut self.objective {
+            Some(objective) => objective.update(&Situation {
+                tick: self.tick,
+                score: self.score,
+                player: focus,
+                destroyed: &self.destroyed,
+                alive: &alive,
+            }),
+            None => return,
         };
 
-        println!("Ship #{} was killed. New score: {}", id, self.score);
+        if self.victory.is_none() {
+            match status {
+                Status::Complete => self.victory = Some(true),
+                Status::Failed => self.victory = Some(false),
+                _ => (),
+            }
+        }
+    }
 
-        // How many ships are left?
-        let alive = self.mobs.iter()
-        // We do not care about the trajectory bounds, so time_delta can be arbitrary
-        .filter(|m| m.get_cache(1.0/60.0 as f64).health > 0.0)
-        .count();
+    fn open_gate(&mut self, name: &str) {
+        let (mut gate, rest): (Vec<Asteroid>, Vec<Asteroid>) = self.asteroids.drain(..)
+            .partition(|a| a.in_group(name));
 
-        if alive == 0 {
-            self.victory = Some(true);
+        self.asteroids = rest;
+        self.gates.entry(name.to_string()).or_default().append(&mut gate);
+    }
+
+    fn close_gate(&mut self, name: &str) {
+        if let Some(mut gate) = self.gates.remove(name) {
+            self.asteroids.append(&mut gate);
         }
     }
 
-    pub fn get_successor_args(&mut self) -> String {
-        match self.victory.unwrap() {
-            true => self.next.to_string(),
-            false => "level1".to_string()
+    fn check_victory(&mut self) {
+        if self.victory.is_some() || self.objective.is_some() || self.is_versus() || self.mobs.is_empty() || !self.spawner.is_finished() {
+            return;
+        }
+
+        if self.count_enemies() == 0 {
+            self.victory = Some(true);
         }
     }
+
+    // The level file's idea of what comes after it, see Campaign for the rest
+    pub fn get_next(&self) -> String {
+        self.next.to_string()
+    }
 }
 
 pub trait Screen {
-    fn set_offset(&mut self, point: Point);
+    fn set_view(&mut self, view: View);
     fn draw_ship(&mut self, ship: &ShipCache, time_delta: f64, tick: u64);
     fn draw_asteroid(&self, asteroid: &Asteroid);
     fn draw_background(&self);
-}
\ No newline at end of file
+    fn draw_debug(&mut self, ship: &ShipCache);
+    fn draw_radar(&self, radar: &Radar);
+    fn draw_widget(&self, widget: &Widget);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::*;
+    use crate::physics::{Circle,Vector};
+    use std::f64::consts::PI;
+
+    // Levels with only a repeating wave are won by clearing what it has sent
+    #[test]
+    fn test_endless_victory() {
+        let mut game = Game::from_json(include_str!("../data/level0.json").to_string()).unwrap();
+        game.seed(0);
+
+        for _ in 0..3600 {
+            // Ram every mob with something heavy enough to finish it
+            for mob in game.mobs.iter_mut().filter(|m| m.get_health() > 0.0) {
+                let circle = Circle::new(mob.get_x() + 1.0, mob.get_y(), 1.0);
+                mob.collision_bounce(circle, Vector::new(PI, 1000.0), 1.0, 1000.0);
+            }
+
+            if !game.update(&[], Point::new(0.0, 0.0), 1.0/60.0) {
+                break;
+            }
+        }
+
+        assert_eq!(game.victory, Some(true));
+    }
+
+    // Levels asking for brains that can't work don't load at all
+    #[test]
+    fn test_bad_brains() {
+        let levels = [
+            r#"{"player": [0, 0.0, 0.0], "mobs": [[1, 100.0, 0.0, [0.0, 0.0], 0.0, {"brain": "nobody"}]]}"#,
+            r#"{"player": [0, 0.0, 0.0], "classes": {"1": "nobody"}}"#,
+            r#"{"player": [0, 0.0, 0.0], "brains": {"broken": {"script": "this is not rhai"}}}"#,
+        ];
+
+        for level in levels.iter() {
+            assert!(Game::from_json(level.to_string()).is_err());
+        }
+
+        assert!(Game::from_json(include_str!("../data/scripted.json").to_string()).is_ok());
+    }
+
+    // Player two starts overlapping player one, both at the given speeds
+    // and heading for each other, with nothing else around
+    fn crash(mode: Mode, speeds: (f64, f64)) -> Game {
+        let level = format!(r#"{{
+            "player": [0, 0.0, 0.0, [0.0, {}]],
+            "players": [[0, 30.0, 0.0, [{}, {}]]]
+        }}"#, speeds.0, PI, speeds.1);
+
+        let mut game = Game::from_json(level).unwrap();
+        game.set_players(2, mode);
+
+        for _ in 0..3 {
+            game.update_players(&[Inputs::new(), Inputs::new()], 1.0/60.0);
+        }
+
+        game
+    }
+
+    // Whoever rams the other to death wins versus, and is paid for it
+    #[test]
+    fn test_versus_winner() {
+        let game = crash(Mode::Versus, (0.0, 5000.0));
+
+        assert_eq!(game.get_player_status()[0].1, 0.0);
+        assert_eq!(game.victory, Some(true));
+        assert_eq!(game.get_winner(), Some(1));
+        assert_eq!(game.players[1].score, VERSUS_POINTS);
+        assert_eq!(game.players[0].score, 0);
+    }
+
+    // Players going down together leave nobody to win
+    #[test]
+    fn test_versus_draw() {
+        let game = crash(Mode::Versus, (5000.0, 5000.0));
+
+        assert!(game.players.iter().all(|p| !p.is_alive()));
+        assert_eq!(game.victory, Some(false));
+        assert_eq!(game.get_winner(), None);
+    }
+
+    // Co-op goes on while anyone is left, and losing a teammate pays nothing
+    #[test]
+    fn test_coop_defeat() {
+        let game = crash(Mode::Coop, (0.0, 5000.0));
+
+        assert!(!game.players[0].is_alive());
+        assert_eq!(game.victory, None);
+        assert_eq!(game.get_winner(), None);
+        assert_eq!(game.players[1].score, 0);
+
+        let game = crash(Mode::Coop, (5000.0, 5000.0));
+
+        assert_eq!(game.victory, Some(false));
+    }
+}
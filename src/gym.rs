@@ -0,0 +1,214 @@
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use crate::ai::{Directive,PuppetBrain};
+use crate::ai::encoding::*;
+use crate::game::*;
+use crate::physics::Point;
+
+const TIME_DELTA: f64 = 1.0 / 60.0;
+const MAX_STEPS: u64 = 60 * 180;
+
+// Rewards for reaching the end of a level
+const VICTORY_REWARD: f64 = 10.0;
+const DEFEAT_REWARD: f64 = -10.0;
+
+pub type Step = (Vec<f64>, f64, bool);
+
+// A gym-style environment around a headless game, with the player ship
+// controlled by the actions passed to step().
+pub struct Gym {
+    data_dir: String,
+    game: Option<Game>,
+    directives: Rc<RefCell<Vec<Directive>>>,
+    max_steps: u64,
+    steps: u64,
+    score: u32,
+    health: f64,
+}
+
+impl Gym {
+    pub fn new(data_dir: &str) -> Gym {
+        Gym {
+            data_dir: data_dir.to_string(),
+            game: None,
+            directives: Rc::new(RefCell::new(Vec::new())),
+            max_steps: MAX_STEPS,
+            steps: 0,
+            score: 0,
+            health: 0.0,
+        }
+    }
+
+    pub fn set_max_steps(&mut self, steps: u64) {
+        self.max_steps = steps;
+    }
+
+    pub fn reset(&mut self, seed: u64, level: &str) -> Vec<f64> {
+        let filename = format!("{}/{}.json", self.data_dir, level);
+        let content = fs::read_to_string(&filename).unwrap_or_else(|_| panic!("File {} not found.", &filename));
+
+        let mut game = Game::from_json(content).expect("Invalid JSON.");
+        game.seed(seed);
+        game.set_player_brain(Box::new(PuppetBrain::new(self.directives.clone())));
+
+        self.steps = 0;
+        self.score = game.get_score();
+        self.health = game.get_player_health();
+        self.directives.replace(Vec::new());
+
+        let observation = game.observe();
+        self.game = Some(game);
+
+        observation
+    }
+
+    pub fn step(&mut self, action: &Action) -> Step {
+        let game = self.game.as_mut().expect("Call reset() before step().");
+
+        if game.get_victory().is_some() || self.steps >= self.max_steps {
+            return (game.observe(), 0.0, true);
+        }
+
+        self.directives.replace(action.directives(TIME_DELTA));
+        game.update(&[], Point::new(0.0, 0.0), TIME_DELTA);
+        self.steps += 1;
+
+        // Reward kills, punish getting hurt
+        let score = game.get_score();
+        let health = game.get_player_health().max(0.0);
+        let mut reward = (score - self.score) as f64 / 100.0 - (self.health - health) / 100.0;
+
+        self.score = score;
+        self.health = health;
+
+        reward += match game.get_victory() {
+            Some(true) => VICTORY_REWARD,
+            Some(false) => DEFEAT_REWARD,
+            None => 0.0,
+        };
+
+        let done = game.get_victory().is_some() || self.steps >= self.max_steps;
+
+        (game.observe(), reward, done)
+    }
+}
+
+enum Command {
+    Reset(u64, String),
+    Step(Action),
+}
+
+struct Worker {
+    commands: Sender<Command>,
+    results: Receiver<Step>,
+    handle: JoinHandle<()>,
+}
+
+// Many independent gyms, each stepped on its own thread
+pub struct BatchGym {
+    workers: Vec<Worker>,
+}
+
+impl BatchGym {
+    pub fn new(data_dir: &str, size: usize) -> BatchGym {
+        let workers = (0..size).map(|_| {
+            let (commands, inbox) = channel::<Command>();
+            let (outbox, results) = channel::<Step>();
+            let data_dir = data_dir.to_string();
+
+            // Games are not Send, so each one has to live and die on its own thread
+            let handle = thread::spawn(move || {
+                let mut gym = Gym::new(&data_dir);
+
+                for command in inbox {
+                    let step = match command {
+                        Command::Reset(seed, level) => (gym.reset(seed, &level), 0.0, false),
+                        Command::Step(action) => gym.step(&action),
+                    };
+
+                    if outbox.send(step).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            Worker {
+                commands: commands,
+                results: results,
+                handle: handle,
+            }
+        }).collect();
+
+        BatchGym {
+            workers: workers,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.workers.is_empty()
+    }
+
+    pub fn reset(&mut self, seeds: &[u64], level: &str) -> Vec<Vec<f64>> {
+        assert_eq!(seeds.len(), self.workers.len(), "Expected one seed per game.");
+
+        for (worker, seed) in self.workers.iter().zip(seeds) {
+            worker.commands.send(Command::Reset(*seed, level.to_string())).unwrap();
+        }
+
+        self.collect().into_iter().map(|s| s.0).collect()
+    }
+
+    pub fn step(&mut self, actions: &[Action]) -> Vec<Step> {
+        assert_eq!(actions.len(), self.workers.len(), "Expected one action per game.");
+
+        for (worker, action) in self.workers.iter().zip(actions) {
+            worker.commands.send(Command::Step(*action)).unwrap();
+        }
+
+        self.collect()
+    }
+
+    fn collect(&self) -> Vec<Step> {
+        self.workers.iter()
+            .map(|w| w.results.recv().expect("A game crashed."))
+            .collect()
+    }
+}
+
+impl Drop for BatchGym {
+    fn drop(&mut self) {
+        for worker in self.workers.drain(..) {
+            // Hanging up makes the worker leave its loop
+            drop(worker.commands);
+            worker.handle.join().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::encoding::*;
+    use crate::gym::*;
+
+    #[test]
+    fn test_batch() {
+        let mut gym = BatchGym::new("data", 3);
+        let observations = gym.reset(&[1, 2, 3], "level1");
+
+        assert_eq!(observations.len(), 3);
+        assert!(observations.iter().all(|o| o.len() == OBSERVATION_SIZE));
+
+        let steps = gym.step(&[Action::Discrete(1), Action::Discrete(0), Action::Continuous(0.5, -0.2)]);
+
+        assert_eq!(steps.len(), 3);
+        assert!(steps.iter().all(|s| s.0.len() == OBSERVATION_SIZE && !s.2));
+    }
+}
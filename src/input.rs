@@ -0,0 +1,511 @@
+use std::collections::HashMap;
+
+use serde::{Serialize,Deserialize};
+
+use crate::physics::Point;
+use crate::storage::*;
+
+// How far an axis has to go to count as braking or aiming
+const DEAD_ZONE: f64 = 0.5;
+// Below this an axis is at rest, sticks rarely sit at exactly 0
+const DRIFT: f64 = 0.15;
+
+#[derive(Clone,Copy,Debug,Serialize,Deserialize,PartialEq,Eq,Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Thrust,
+    Brake,
+    Left,
+    Right,
+    // Fly towards the cursor
+    Seek,
+    // Handled by the frontends rather than the game
+    Pause,
+    Debug,
+    ZoomIn,
+    ZoomOut,
+}
+
+impl Action {
+    pub fn is_global(&self) -> bool {
+        matches!(self, Action::Pause | Action::Debug | Action::ZoomIn | Action::ZoomOut)
+    }
+}
+
+// Moving around a menu, whatever it is done with
+#[derive(Clone,Copy,Debug,Deserialize,PartialEq,Eq,Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum MenuInput {
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    Back,
+}
+
+#[derive(Clone,Copy,Debug,Deserialize,PartialEq,Eq,Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Axis {
+    // From 0 to 1
+    Thrust,
+    // From -1 (left) to 1 (right)
+    Rotate,
+    // The other stick in twin-stick mode, pointing where to face
+    AimX,
+    AimY,
+}
+
+// The controls of one player, as the frontend sees them
+#[derive(Clone,Debug)]
+pub struct Inputs {
+    pub pressed: Vec<Action>,
+    pub axes: HashMap<Axis, f64>,
+    // On screen, in the pixels the screen draws in
+    pub cursor: Point,
+    // Pressed since the last tick, and of those, already let go of again
+    fresh: Vec<Action>,
+    pub taps: Vec<Action>,
+}
+
+impl Inputs {
+    pub fn new() -> Inputs {
+        Inputs {
+            pressed: Vec::new(),
+            axes: HashMap::new(),
+            cursor: Point::new(0.0, 0.0),
+            fresh: Vec::new(),
+            taps: Vec::new(),
+        }
+    }
+
+    pub fn press(&mut self, action: Action) {
+        if !self.pressed.contains(&action) {
+            self.pressed.push(action);
+            self.fresh.push(action);
+        }
+    }
+
+    pub fn release(&mut self, action: Action) {
+        if self.pressed.contains(&action) && self.fresh.contains(&action) && !self.taps.contains(&action) {
+            self.taps.push(action);
+        }
+
+        self.pressed.retain(|a| *a != action);
+    }
+
+    pub fn move_axis(&mut self, axis: Axis, value: f64) {
+        self.axes.insert(axis, value);
+    }
+
+    pub fn move_cursor(&mut self, x: f64, y: f64) {
+        self.cursor = Point::new(x, y);
+    }
+
+    // Start over for the next tick, returning how things stand
+    pub fn tick(&mut self) -> Inputs {
+        let ret = self.clone();
+
+        self.fresh.clear();
+        self.taps.clear();
+
+        ret
+    }
+}
+
+impl Default for Inputs {
+    fn default() -> Inputs {
+        Inputs::new()
+    }
+}
+
+// What one player did with their controls during a tick, for brains
+#[derive(Clone,Debug)]
+pub struct InputState {
+    pub held: Vec<Action>,
+    // Went down or came up during the tick, a quick tap being both
+    pub pressed: Vec<Action>,
+    pub released: Vec<Action>,
+    pub axes: HashMap<Axis, f64>,
+    // In the world, rather than on screen
+    pub cursor: Point,
+}
+
+impl InputState {
+    pub fn new() -> InputState {
+        InputState {
+            held: Vec::new(),
+            pressed: Vec::new(),
+            released: Vec::new(),
+            axes: HashMap::new(),
+            cursor: Point::new(0.0, 0.0),
+        }
+    }
+
+    // Compared to what was held during the last tick
+    pub fn from_inputs(inputs: &Inputs, last: &InputState, cursor: Point) -> InputState {
+        let mut pressed: Vec<Action> = inputs.pressed.iter()
+            .filter(|a| !last.held.contains(a))
+            .cloned()
+            .collect();
+        let mut released: Vec<Action> = last.held.iter()
+            .filter(|a| !inputs.pressed.contains(a))
+            .cloned()
+            .collect();
+
+        for action in inputs.taps.iter() {
+            if !pressed.contains(action) {
+                pressed.push(*action);
+            }
+
+            if !released.contains(action) {
+                released.push(*action);
+            }
+        }
+
+        InputState {
+            held: inputs.pressed.to_vec(),
+            pressed: pressed,
+            released: released,
+            axes: inputs.axes.clone(),
+            cursor: cursor,
+        }
+    }
+
+    pub fn is_held(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+
+    pub fn was_pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn was_released(&self, action: Action) -> bool {
+        self.released.contains(&action)
+    }
+
+    pub fn get_axis(&self, axis: Axis) -> f64 {
+        match *self.axes.get(&axis).unwrap_or(&0.0) {
+            v if v.abs() < DRIFT => 0.0,
+            v => v.clamp(-1.0, 1.0),
+        }
+    }
+
+    // Share of full thrust, a button being all the way
+    pub fn get_thrust(&self) -> f64 {
+        match self.is_held(Action::Thrust) {
+            true => 1.0,
+            false => self.get_axis(Axis::Thrust).max(0.0),
+        }
+    }
+
+    // Share of full turning speed, negative to the left
+    pub fn get_rotation(&self) -> f64 {
+        match (self.is_held(Action::Left), self.is_held(Action::Right)) {
+            (true, false) => -1.0,
+            (false, true) => 1.0,
+            _ => self.get_axis(Axis::Rotate),
+        }
+    }
+
+    // Pulling the thrust stick back brakes
+    pub fn is_braking(&self) -> bool {
+        self.is_held(Action::Brake) || self.get_axis(Axis::Thrust) < -DEAD_ZONE
+    }
+
+    // Where the aiming stick points, if it is pushed far enough
+    pub fn get_aim(&self) -> Option<f64> {
+        let x = self.get_axis(Axis::AimX);
+        let y = self.get_axis(Axis::AimY);
+
+        match x.hypot(y) > DEAD_ZONE {
+            true => Some(y.atan2(x)),
+            false => None,
+        }
+    }
+}
+
+impl Default for InputState {
+    fn default() -> InputState {
+        InputState::new()
+    }
+}
+
+// Turns keys, buttons and sticks into what each player wants to do
+pub struct Controls {
+    args: BindingsArgs,
+    pub players: Vec<Inputs>,
+    // Whose sticks use the twin-stick layout
+    twin_stick: Vec<bool>,
+    // Sticks pushed past the dead zone in a menu, by gamepad and axis
+    menu_axes: HashMap<(u32, u32), bool>,
+}
+
+impl Controls {
+    pub fn new(args: BindingsArgs, players: usize) -> Controls {
+        let mut ret = Controls {
+            args: args,
+            players: Vec::new(),
+            twin_stick: Vec::new(),
+            menu_axes: HashMap::new(),
+        };
+
+        ret.set_players(players);
+        ret
+    }
+
+    pub fn from_json(s: &str, players: usize) -> serde_json::Result<Controls> {
+        Ok(Controls::new(serde_json::from_str(s)?, players))
+    }
+
+    pub fn set_players(&mut self, count: usize) {
+        self.players = (0..count.max(1)).map(|_| Inputs::new()).collect();
+        self.twin_stick = (0..count.max(1))
+            .map(|i| self.args.players.get(i).map(|p| p.twin_stick).unwrap_or(false))
+            .collect();
+    }
+
+    // One stick aims and the other thrusts, instead of turning with one
+    pub fn set_twin_stick(&mut self, player: usize, on: bool) {
+        if let Some(twin_stick) = self.twin_stick.get_mut(player) {
+            *twin_stick = on;
+            self.players[player].axes.clear();
+        }
+    }
+
+    pub fn count_players(&self) -> usize {
+        self.players.len()
+    }
+
+    // Returns actions like pause, which are up to the frontend
+    pub fn key_pressed(&mut self, key: &str) -> Option<Action> {
+        if let Some(action) = self.args.keys.get(key) {
+            return Some(*action);
+        }
+
+        let (player, action) = self.find_key(key)?;
+        self.apply(player, action, true)
+    }
+
+    pub fn key_released(&mut self, key: &str) {
+        if let Some((player, action)) = self.find_key(key) {
+            self.apply(player, action, false);
+        }
+    }
+
+    pub fn button_pressed(&mut self, gamepad: u32, button: u32) -> Option<Action> {
+        let player = self.find_gamepad(gamepad)?;
+        let action = *self.args.buttons.get(&button)?;
+        self.apply(player, action, true)
+    }
+
+    // Buttons that say how far down they are, like triggers
+    pub fn trigger_moved(&mut self, gamepad: u32, button: u32, value: f64) {
+        let player = self.find_gamepad(gamepad);
+        let binding = self.args.triggers.get(&button).cloned();
+
+        if let (Some(player), Some(binding)) = (player, binding) {
+            let value = if binding.invert { -value } else { value };
+            self.players[player].move_axis(binding.axis, value);
+        }
+    }
+
+    pub fn button_released(&mut self, gamepad: u32, button: u32) {
+        let player = self.find_gamepad(gamepad);
+        let action = self.args.buttons.get(&button).cloned();
+
+        if let (Some(player), Some(action)) = (player, action) {
+            self.apply(player, action, false);
+        }
+    }
+
+    pub fn axis_moved(&mut self, gamepad: u32, axis: u32, value: f64) {
+        let player = self.find_gamepad(gamepad);
+        let binding = match player.map(|p| self.twin_stick[p]) {
+            Some(true) => self.args.twin_stick.get(&axis).cloned(),
+            _ => self.args.axes.get(&axis).cloned(),
+        };
+
+        if let (Some(player), Some(binding)) = (player, binding) {
+            let value = if binding.invert { -value } else { value };
+            self.players[player].move_axis(binding.axis, value);
+        }
+    }
+
+    // While a menu is open, keys and buttons move around it instead
+    pub fn menu_key(&self, key: &str) -> Option<MenuInput> {
+        self.args.menu.keys.get(key).cloned()
+    }
+
+    pub fn menu_button(&self, button: u32) -> Option<MenuInput> {
+        self.args.menu.buttons.get(&button).cloned()
+    }
+
+    // A stick counts once each time it is pushed past the dead zone
+    pub fn menu_axis(&mut self, gamepad: u32, axis: u32, value: f64) -> Option<MenuInput> {
+        let binding = self.args.menu.axes.get(&axis).cloned()?;
+        let pushed = value.abs() > DEAD_ZONE;
+        let was_pushed = self.menu_axes.insert((gamepad, axis), pushed).unwrap_or(false);
+
+        match (pushed, was_pushed) {
+            (true, false) if value < 0.0 => Some(binding.0),
+            (true, false) => Some(binding.1),
+            _ => None,
+        }
+    }
+
+    // Keys of a player for an action, in no particular order
+    pub fn get_keys(&self, player: usize, action: Action) -> Vec<String> {
+        match self.args.players.get(player) {
+            Some(p) => p.keys.iter()
+                .filter(|(_, a)| **a == action)
+                .map(|(k, _)| k.to_string())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Gives a key to a player for an action, instead of the keys it had.
+    // Keys not tied to a player, like pause, can't be taken.
+    pub fn rebind(&mut self, player: usize, key: &str, action: Action) -> bool {
+        if action.is_global() || self.args.keys.contains_key(key) || player >= self.args.players.len() {
+            return false;
+        }
+
+        for p in self.args.players.iter_mut() {
+            p.keys.remove(key);
+        }
+
+        let keys = &mut self.args.players[player].keys;
+        keys.retain(|_, a| *a != action);
+        keys.insert(key.to_string(), action);
+
+        true
+    }
+
+    // The mouse belongs to player one
+    pub fn move_cursor(&mut self, x: f64, y: f64) {
+        self.players[0].move_cursor(x, y);
+    }
+
+    pub fn get_cursor(&self) -> Point {
+        self.players[0].cursor
+    }
+
+    // What every player is holding, in order
+    pub fn get_actions(&self) -> Vec<Vec<Action>> {
+        self.players.iter().map(|p| p.pressed.to_vec()).collect()
+    }
+
+    // Every player's controls for the tick about to run, in order
+    pub fn tick(&mut self) -> Vec<Inputs> {
+        self.players.iter_mut().map(|p| p.tick()).collect()
+    }
+
+    fn apply(&mut self, player: usize, action: Action, pressed: bool) -> Option<Action> {
+        if action.is_global() {
+            return Some(action).filter(|_| pressed);
+        }
+
+        match pressed {
+            true => self.players[player].press(action),
+            false => self.players[player].release(action),
+        }
+
+        None
+    }
+
+    fn find_key(&self, key: &str) -> Option<(usize, Action)> {
+        self.args.players.iter()
+            .enumerate()
+            .find_map(|(i, p)| p.keys.get(key).map(|a| (self.seat(i), *a)))
+    }
+
+    fn find_gamepad(&self, gamepad: u32) -> Option<usize> {
+        self.args.players.iter()
+            .position(|p| p.gamepad == Some(gamepad))
+            .map(|i| self.seat(i))
+    }
+
+    // Bindings of players who aren't playing go to player one, so
+    // someone alone gets the whole keyboard
+    fn seat(&self, player: usize) -> usize {
+        match player < self.players.len() {
+            true => player,
+            false => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input::*;
+
+    const BINDINGS: &str = r#"{
+        "keys": {"p": "pause"},
+        "players": [
+            {"keys": {"w": "thrust"}, "gamepad": 0},
+            {"keys": {"arrowup": "thrust", "arrowleft": "left"}, "gamepad": 1}
+        ],
+        "buttons": {"9": "pause"},
+        "triggers": {"7": {"axis": "thrust"}},
+        "axes": {"0": {"axis": "rotate"}},
+        "twin_stick": {"1": {"axis": "thrust", "invert": true}, "2": {"axis": "aimx"}, "3": {"axis": "aimy"}}
+    }"#;
+
+    fn state(inputs: &Inputs) -> InputState {
+        InputState::from_inputs(inputs, &InputState::new(), Point::new(0.0, 0.0))
+    }
+
+    #[test]
+    fn test_controls() {
+        let mut controls = Controls::from_json(BINDINGS, 1).unwrap();
+
+        // Alone, player one gets everyone's keys and gamepads
+        controls.key_pressed("arrowup");
+        controls.axis_moved(1, 0, -0.8);
+        assert_eq!(controls.get_actions(), vec![vec![Action::Thrust]]);
+        assert_eq!(state(&controls.players[0]).get_rotation(), -0.8);
+
+        controls.set_players(2);
+        assert_eq!(controls.key_pressed("p"), Some(Action::Pause));
+        assert_eq!(controls.button_pressed(1, 9), Some(Action::Pause));
+
+        controls.key_pressed("arrowleft");
+        controls.trigger_moved(0, 7, 0.4);
+        controls.axis_moved(0, 0, 0.1);
+        assert_eq!(controls.get_actions(), vec![vec![], vec![Action::Left]]);
+        assert_eq!(state(&controls.players[0]).get_thrust(), 0.4);
+        assert_eq!(state(&controls.players[0]).get_rotation(), 0.0);
+
+        // Pull back to brake, push the other stick up to face up
+        controls.set_twin_stick(1, true);
+        controls.axis_moved(1, 1, 0.9);
+        controls.axis_moved(1, 3, -1.0);
+        assert!(state(&controls.players[1]).is_braking());
+        assert_eq!(state(&controls.players[1]).get_aim(), Some(-std::f64::consts::FRAC_PI_2));
+    }
+
+    #[test]
+    fn test_input_state() {
+        let mut inputs = Inputs::new();
+        let cursor = Point::new(0.0, 0.0);
+
+        inputs.press(Action::Thrust);
+        let first = InputState::from_inputs(&inputs.tick(), &InputState::new(), cursor);
+        assert!(first.was_pressed(Action::Thrust) && first.is_held(Action::Thrust));
+
+        // Still held, then a tap in between ticks
+        inputs.press(Action::Brake);
+        inputs.release(Action::Brake);
+        let second = InputState::from_inputs(&inputs.tick(), &first, cursor);
+        assert!(!second.was_pressed(Action::Thrust));
+        assert!(second.was_pressed(Action::Brake) && second.was_released(Action::Brake));
+        assert!(!second.is_held(Action::Brake));
+
+        inputs.release(Action::Thrust);
+        let third = InputState::from_inputs(&inputs.tick(), &second, cursor);
+        assert!(third.was_released(Action::Thrust));
+        assert!(!third.was_pressed(Action::Brake) && !third.was_released(Action::Brake));
+    }
+}
@@ -9,15 +9,32 @@ extern crate console_error_panic_hook;
 #[cfg(target_arch = "wasm32")]
 extern crate wasm_bindgen;
 
+pub mod achievement;
 pub mod ai;
 pub mod asteroid;
 pub mod camera;
+pub mod campaign;
+pub mod difficulty;
 pub mod game;
+pub mod input;
+pub mod menu;
+pub mod minimap;
 pub mod physics;
+pub mod scoring;
 pub mod ship;
 pub mod storage;
 pub mod spawner;
+pub mod statistics;
+pub mod objective;
+pub mod trigger;
 pub mod broadcast;
+pub mod widget;
+pub mod world;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod gym;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tournament;
 
 #[cfg(target_arch = "wasm32")]
 pub mod wasm_bindings;
@@ -27,9 +44,9 @@ use wasm_bindgen::prelude::*;
 
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
-pub fn start(s: &js_sys::JsString, ctx: web_sys::CanvasRenderingContext2d) -> wasm_bindings::wrapper::GameWrapper {
+pub fn start(level: &js_sys::JsString, s: &js_sys::JsString, campaign: &js_sys::JsString, achievements: &js_sys::JsString, bindings: &js_sys::JsString, ctx: web_sys::CanvasRenderingContext2d) -> wasm_bindings::wrapper::GameWrapper {
     std::panic::set_hook(Box::new(console_error_panic_hook::hook));
 
     //panic!("ohnoes");
-    wasm_bindings::wrapper::GameWrapper::new(String::from(s), ctx)
+    wasm_bindings::wrapper::GameWrapper::new(String::from(level), String::from(s), String::from(campaign), String::from(achievements), String::from(bindings), ctx)
 }
@@ -3,17 +3,37 @@
 extern crate serde;
 extern crate serde_json;
 
+pub mod achievement;
 pub mod ai;
 pub mod asteroid;
 pub mod camera;
+pub mod campaign;
+pub mod difficulty;
 pub mod game;
+pub mod input;
+pub mod menu;
+pub mod minimap;
+pub mod gym;
 pub mod physics;
+pub mod scoring;
 pub mod ship;
 pub mod spawner;
+pub mod statistics;
+pub mod objective;
+pub mod trigger;
 pub mod storage;
 pub mod broadcast;
+pub mod widget;
+pub mod world;
 pub mod piston_bindings;
+pub mod tournament;
 
 fn main() {
-    piston_bindings::wrapper::main();
+    let args: Vec<String> = std::env::args().collect();
+
+    // wamsbin tournament [data/tournament.json]
+    match args.get(1).map(|s| s.as_str()) {
+        Some("tournament") => tournament::main(args.get(2).map(|s| s.as_str()).unwrap_or("data/tournament.json")),
+        _ => piston_bindings::wrapper::main(),
+    }
 }
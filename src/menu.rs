@@ -0,0 +1,439 @@
+use serde::{Serialize,Deserialize};
+
+use crate::campaign::Campaign;
+use crate::difficulty::Difficulty;
+use crate::input::*;
+use crate::physics::Point;
+use crate::storage::*;
+use crate::widget::*;
+
+const SAVE_KEY: &str = "settings";
+const TITLE: &str = "Well-Adjusted, Mature Spaceships";
+const WIDTH: f64 = 360.0;
+// What the bindings page lets player one change
+const REBINDABLE: [Action; 5] = [Action::Thrust, Action::Brake, Action::Left, Action::Right, Action::Seek];
+// None being whatever the level says
+const DIFFICULTIES: [Option<Difficulty>; 4] = [None, Some(Difficulty::Easy), Some(Difficulty::Normal), Some(Difficulty::Hard)];
+
+// What is kept between runs
+#[derive(Clone,Debug,Default,Serialize,Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // Overrides the levels' own
+    pub difficulty: Option<Difficulty>,
+    // Player one's keys, as rebound in the menu
+    pub keys: Vec<(Action, String)>,
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Page {
+    Main,
+    Levels,
+    Options,
+    Bindings,
+    Pause,
+    // How the last level went, over the start of the next one
+    Summary,
+}
+
+// What picking something in the menu asks of the frontend
+#[derive(Clone,Debug,PartialEq)]
+pub enum Choice {
+    Play(String),
+    Resume,
+    Restart,
+    // Leave the level for the main menu
+    Quit,
+    // Leave the game altogether
+    Exit,
+    Difficulty(Option<Difficulty>),
+}
+
+#[derive(Clone,Debug,PartialEq)]
+enum Item {
+    Open(Page),
+    Choose(Choice),
+    Difficulty,
+    Rebind(Action),
+    Back,
+    // Shown, but can't be picked, e.g. locked levels
+    Locked,
+}
+
+// Both frontends open it over the game, pass it keys, buttons and the
+// mouse while it is open, and do what the choices it returns say
+pub struct Menu {
+    // Open pages, the top one showing, with what is selected on each
+    pages: Vec<(Page, usize)>,
+    // Waiting for a key to bind to this
+    capturing: Option<Action>,
+    // Frontends that can't quit, like the browser, leave out the option
+    can_exit: bool,
+    start: String,
+    levels: Vec<(String, bool, Option<u32>)>,
+    keys: Vec<(Action, Vec<String>)>,
+    summary: Vec<String>,
+    pub settings: Settings,
+    store: Box<dyn Store>,
+}
+
+impl Menu {
+    pub fn new(store: Box<dyn Store>, can_exit: bool) -> Menu {
+        let settings = store.load(SAVE_KEY)
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+
+        Menu {
+            pages: Vec::new(),
+            capturing: None,
+            can_exit: can_exit,
+            start: String::new(),
+            levels: Vec::new(),
+            keys: Vec::new(),
+            summary: Vec::new(),
+            settings: settings,
+            store: store,
+        }
+    }
+
+    // Levels to pick from, and where playing starts
+    pub fn set_campaign(&mut self, campaign: &Campaign) {
+        self.start = campaign.get_start();
+        self.levels = campaign.get_levels();
+    }
+
+    // Puts back the keys rebound in an earlier run
+    pub fn load_bindings(&mut self, controls: &mut Controls) {
+        for (action, key) in self.settings.keys.iter() {
+            controls.rebind(0, key, *action);
+        }
+
+        self.read_keys(controls);
+    }
+
+    // What the summary page lists, e.g. Statistics::summary
+    pub fn set_summary(&mut self, lines: Vec<String>) {
+        self.summary = lines;
+    }
+
+    pub fn is_open(&self) -> bool {
+        !self.pages.is_empty()
+    }
+
+    pub fn get_page(&self) -> Option<Page> {
+        self.pages.last().map(|p| p.0)
+    }
+
+    // On its own, closing whatever else was open
+    pub fn open(&mut self, page: Page) {
+        self.pages.clear();
+        self.capturing = None;
+        self.push(page);
+    }
+
+    pub fn close(&mut self) {
+        self.pages.clear();
+        self.capturing = None;
+    }
+
+    pub fn key_pressed(&mut self, key: &str, controls: &mut Controls) -> Option<Choice> {
+        let input = controls.menu_key(key);
+
+        if let Some(action) = self.capturing {
+            self.capturing = None;
+
+            if input != Some(MenuInput::Back) && controls.rebind(0, key, action) {
+                self.settings.keys.retain(|k| k.0 != action && k.1 != key);
+                self.settings.keys.push((action, key.to_string()));
+                self.save();
+                self.read_keys(controls);
+            }
+
+            return None;
+        }
+
+        self.press(input?)
+    }
+
+    pub fn button_pressed(&mut self, button: u32, controls: &Controls) -> Option<Choice> {
+        self.capturing = None;
+        self.press(controls.menu_button(button)?)
+    }
+
+    pub fn axis_moved(&mut self, gamepad: u32, axis: u32, value: f64, controls: &mut Controls) -> Option<Choice> {
+        self.press(controls.menu_axis(gamepad, axis, value)?)
+    }
+
+    // Hovering selects, in logical pixels on a screen this big
+    pub fn cursor_moved(&mut self, cursor: Point, width: f64, height: f64) {
+        if let Some(i) = self.item_at(cursor, width, height) {
+            self.select(i);
+        }
+    }
+
+    pub fn clicked(&mut self, cursor: Point, width: f64, height: f64) -> Option<Choice> {
+        let i = self.item_at(cursor, width, height)?;
+        self.select(i);
+        self.press(MenuInput::Select)
+    }
+
+    pub fn press(&mut self, input: MenuInput) -> Option<Choice> {
+        let items = self.items();
+        let selected = self.pages.last()?.1;
+        let item = items.get(selected).map(|i| i.1.clone())?;
+
+        match input {
+            MenuInput::Up => self.step(&items, -1),
+            MenuInput::Down => self.step(&items, 1),
+            MenuInput::Left => return self.adjust(&item, -1),
+            MenuInput::Right => return self.adjust(&item, 1),
+            MenuInput::Select => return self.activate(item),
+            MenuInput::Back => return self.back(),
+        }
+
+        None
+    }
+
+    // For the screen, centered over everything else
+    pub fn get_widget(&self) -> Widget {
+        let title = match self.get_page() {
+            Some(Page::Levels) => "Levels",
+            Some(Page::Options) => "Options",
+            Some(Page::Bindings) => "Bindings",
+            Some(Page::Pause) => "Paused",
+            Some(Page::Summary) => "Level over",
+            _ => TITLE,
+        };
+
+        let selected = self.pages.last().map(|p| p.1);
+
+        self.items().iter()
+            .enumerate()
+            .fold(
+                Widget::new().panel()
+                    .anchor(Anchor::Center, 0.0, 0.0)
+                    .size(Size::Fixed(WIDTH), Size::Fit)
+                    .layout(Layout::Column(4.0))
+                    .child(Widget::text(title)),
+                |w, (i, item)| {
+                    let mut row = Widget::text(&item.0).size(Size::Relative(1.0), Size::Fit).tag(i);
+                    row.background = selected == Some(i);
+                    w.child(row)
+                }
+            )
+    }
+
+    fn push(&mut self, page: Page) {
+        self.pages.push((page, 0));
+
+        let items = self.items();
+        if let Some(i) = items.iter().position(|i| i.1 != Item::Locked) {
+            self.select(i);
+        }
+    }
+
+    fn select(&mut self, i: usize) {
+        if let Some(page) = self.pages.last_mut() {
+            page.1 = i;
+        }
+    }
+
+    // To the next item that can be picked, round the end
+    fn step(&mut self, items: &[(String, Item)], by: i64) {
+        let n = items.len() as i64;
+        let mut i = self.pages.last().map(|p| p.1).unwrap_or(0) as i64;
+
+        for _ in 0..n {
+            i = (i + by).rem_euclid(n);
+
+            if items[i as usize].1 != Item::Locked {
+                break;
+            }
+        }
+
+        self.select(i as usize);
+    }
+
+    fn activate(&mut self, item: Item) -> Option<Choice> {
+        match item {
+            Item::Open(page) => self.push(page),
+            Item::Choose(choice) => return Some(choice),
+            Item::Difficulty => return self.adjust(&item, 1),
+            Item::Rebind(action) => self.capturing = Some(action),
+            Item::Back => return self.back(),
+            Item::Locked => (),
+        }
+
+        None
+    }
+
+    // Goes round past either end
+    fn adjust(&mut self, item: &Item, by: i64) -> Option<Choice> {
+        let choice = match item {
+            Item::Difficulty => {
+                let i = DIFFICULTIES.iter().position(|d| *d == self.settings.difficulty).unwrap_or(0) as i64;
+                self.settings.difficulty = DIFFICULTIES[(i + by).rem_euclid(DIFFICULTIES.len() as i64) as usize];
+                Choice::Difficulty(self.settings.difficulty)
+            },
+            _ => return None,
+        };
+
+        self.save();
+        Some(choice)
+    }
+
+    // Backing out of the pause menu or the summary goes back to the game
+    fn back(&mut self) -> Option<Choice> {
+        match self.pages.len() {
+            0 => None,
+            1 if self.get_page() == Some(Page::Pause) || self.get_page() == Some(Page::Summary) => Some(Choice::Resume),
+            1 => None,
+            _ => {
+                self.pages.pop();
+                None
+            },
+        }
+    }
+
+    fn item_at(&self, cursor: Point, width: f64, height: f64) -> Option<usize> {
+        let i = Widget::tag_at(&self.get_widget().layout_on(width, height), cursor)?;
+
+        match self.items().get(i) {
+            Some((_, Item::Locked)) => None,
+            _ => Some(i),
+        }
+    }
+
+    // What the showing page lists, with labels
+    fn items(&self) -> Vec<(String, Item)> {
+        let mut ret = match self.get_page() {
+            None => return Vec::new(),
+            Some(Page::Main) => vec![
+                ("Play".to_string(), Item::Choose(Choice::Play(self.start.to_string()))),
+                ("Levels".to_string(), Item::Open(Page::Levels)),
+                ("Options".to_string(), Item::Open(Page::Options)),
+            ],
+            Some(Page::Levels) => self.levels.iter()
+                .map(|(level, unlocked, best)| match (unlocked, best) {
+                    (false, _) => (format!("{:<16}locked", level), Item::Locked),
+                    (true, Some(best)) => (format!("{:<16}{:>6}", level, best), Item::Choose(Choice::Play(level.to_string()))),
+                    (true, None) => (level.to_string(), Item::Choose(Choice::Play(level.to_string()))),
+                })
+                .collect(),
+            Some(Page::Options) => vec![
+                (format!("{:<12}< {} >", "Difficulty", match self.settings.difficulty {
+                    Some(d) => format!("{:?}", d),
+                    None => "Level".to_string(),
+                }), Item::Difficulty),
+                ("Bindings".to_string(), Item::Open(Page::Bindings)),
+            ],
+            Some(Page::Bindings) => self.keys.iter()
+                .map(|(action, keys)| {
+                    let keys = match self.capturing == Some(*action) {
+                        true => "press a key".to_string(),
+                        false => keys.join(", "),
+                    };
+
+                    (format!("{:<12}{}", format!("{:?}", action), keys), Item::Rebind(*action))
+                })
+                .collect(),
+            Some(Page::Pause) => vec![
+                ("Resume".to_string(), Item::Choose(Choice::Resume)),
+                ("Restart".to_string(), Item::Choose(Choice::Restart)),
+                ("Options".to_string(), Item::Open(Page::Options)),
+                ("Quit to menu".to_string(), Item::Choose(Choice::Quit)),
+            ],
+            Some(Page::Summary) => self.summary.iter()
+                .map(|line| (line.to_string(), Item::Locked))
+                .chain(vec![("Continue".to_string(), Item::Choose(Choice::Resume))])
+                .collect(),
+        };
+
+        match self.get_page() {
+            Some(Page::Main) if self.can_exit => ret.push(("Quit".to_string(), Item::Choose(Choice::Exit))),
+            Some(Page::Main) | Some(Page::Pause) | Some(Page::Summary) => (),
+            _ => ret.push(("Back".to_string(), Item::Back)),
+        }
+
+        ret
+    }
+
+    fn read_keys(&mut self, controls: &Controls) {
+        self.keys = REBINDABLE.iter()
+            .map(|a| {
+                let mut keys = controls.get_keys(0, *a);
+                keys.sort();
+                (*a, keys)
+            })
+            .collect();
+    }
+
+    fn save(&mut self) {
+        match serde_json::to_string(&self.settings) {
+            Ok(s) => self.store.save(SAVE_KEY, &s),
+            Err(e) => println!("Could not save the settings: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input::*;
+    use crate::menu::*;
+
+    const BINDINGS: &str = r#"{
+        "keys": {"p": "pause"},
+        "players": [{"keys": {"w": "thrust"}}],
+        "menu": {"keys": {"arrowup": "up", "arrowdown": "down", "enter": "select", "escape": "back"}}
+    }"#;
+
+    #[test]
+    fn test_menu() {
+        let mut controls = Controls::from_json(BINDINGS, 1).unwrap();
+        let mut menu = Menu::new(Box::new(MemoryStore::default()), true);
+        menu.load_bindings(&mut controls);
+
+        // Resume, restart, options, then round to resume again
+        menu.open(Page::Pause);
+        menu.key_pressed("arrowup", &mut controls);
+        assert_eq!(menu.key_pressed("enter", &mut controls), Some(Choice::Quit));
+        menu.key_pressed("arrowdown", &mut controls);
+        assert_eq!(menu.key_pressed("escape", &mut controls), Some(Choice::Resume));
+
+        // Options, then its bindings, then rebind thrust
+        menu.key_pressed("arrowup", &mut controls);
+        menu.key_pressed("arrowup", &mut controls);
+        menu.key_pressed("enter", &mut controls);
+        assert_eq!(menu.get_page(), Some(Page::Options));
+        assert_eq!(menu.key_pressed("enter", &mut controls), Some(Choice::Difficulty(Some(Difficulty::Easy))));
+
+        menu.key_pressed("arrowdown", &mut controls);
+        menu.key_pressed("enter", &mut controls);
+        menu.key_pressed("enter", &mut controls);
+        menu.key_pressed("i", &mut controls);
+        assert_eq!(controls.get_keys(0, Action::Thrust), vec!["i".to_string()]);
+        assert_eq!(menu.settings.keys, vec![(Action::Thrust, "i".to_string())]);
+
+        // Keys like pause stay where they are
+        menu.key_pressed("enter", &mut controls);
+        menu.key_pressed("p", &mut controls);
+        assert_eq!(controls.get_keys(0, Action::Thrust), vec!["i".to_string()]);
+
+        menu.key_pressed("escape", &mut controls);
+        menu.key_pressed("escape", &mut controls);
+        assert_eq!(menu.get_page(), Some(Page::Pause));
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut controls = Controls::from_json(BINDINGS, 1).unwrap();
+        let mut menu = Menu::new(Box::new(MemoryStore::default()), true);
+
+        // Only continuing can be picked
+        menu.set_summary(vec!["Score: 100".to_string(), "Time: 0:42".to_string()]);
+        menu.open(Page::Summary);
+        menu.key_pressed("arrowup", &mut controls);
+        assert_eq!(menu.key_pressed("enter", &mut controls), Some(Choice::Resume));
+        assert_eq!(menu.key_pressed("escape", &mut controls), Some(Choice::Resume));
+    }
+}
@@ -0,0 +1,167 @@
+use std::str::FromStr;
+
+use crate::game::Game;
+use crate::physics::{Point,Rectangle};
+use crate::ship::*;
+
+// Without world bounds the radar shows this many screens' worth around the camera
+const RANGE: f64 = 3.0;
+// Ships are dots of the same size, however far out the radar is zoomed
+const SHIP_SIZE: f64 = 2.5;
+const PLAYER_SIZE: f64 = 3.5;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl FromStr for Corner {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Corner, String> {
+        match s {
+            "topleft" => Ok(Corner::TopLeft),
+            "topright" => Ok(Corner::TopRight),
+            "bottomleft" => Ok(Corner::BottomLeft),
+            "bottomright" => Ok(Corner::BottomRight),
+            _ => Err(format!("Unknown corner: {}", s)),
+        }
+    }
+}
+
+// Where the radar goes and how big it is, in logical pixels
+#[derive(Clone,Copy,Debug)]
+pub struct Minimap {
+    pub corner: Corner,
+    // Of the longer side, the other follows the shape of the world
+    pub size: f64,
+    // From the edges of the screen
+    pub margin: f64,
+}
+
+impl Minimap {
+    pub fn new(corner: Corner, size: f64) -> Minimap {
+        Minimap {
+            corner: corner,
+            size: size,
+            margin: 10.0,
+        }
+    }
+
+    // A snapshot of the game for a screen this big, in logical pixels
+    pub fn scan(&self, game: &Game, width: f64, height: f64) -> Radar {
+        let visible = game.get_visible();
+
+        // The whole world if it has bounds, otherwise around the camera
+        let area = match game.get_world() {
+            Some(world) => world.bounds,
+            None => Rectangle::new(
+                visible.x + visible.width * (1.0 - RANGE) / 2.0,
+                visible.y + visible.height * (1.0 - RANGE) / 2.0,
+                visible.width * RANGE,
+                visible.height * RANGE
+            ),
+        };
+
+        let scale = self.size / area.width.max(area.height);
+        let [w, h] = [area.width * scale, area.height * scale];
+
+        let x = match self.corner {
+            Corner::TopLeft | Corner::BottomLeft => self.margin,
+            Corner::TopRight | Corner::BottomRight => width - w - self.margin,
+        };
+        let y = match self.corner {
+            Corner::TopLeft | Corner::TopRight => self.margin,
+            Corner::BottomLeft | Corner::BottomRight => height - h - self.margin,
+        };
+
+        let rect = Rectangle::new(x, y, w, h);
+        let to_radar = |p: Point| Point::new(x + (p.x - area.x) * scale, y + (p.y - area.y) * scale);
+        let inside = |p: Point| p.x >= x && p.x <= x + w && p.y >= y && p.y <= y + h;
+
+        let mut blips = Vec::new();
+
+        for asteroid in game.get_asteroids().iter() {
+            let circle = asteroid.get_circle();
+            let p = to_radar(Point::new(circle.x, circle.y));
+
+            if inside(p) {
+                blips.push(Blip { point: p, r: (circle.r * scale).max(1.0), category: 0, kind: BlipKind::Asteroid });
+            }
+        }
+
+        // Players last, so they are drawn on top
+        let mut ships: Vec<&ShipCache> = game.get_actors().values()
+            .filter(|s| s.health > 0.0)
+            .collect();
+        ships.sort_by_key(|s| game.is_player(s.id));
+
+        for ship in ships {
+            let p = to_radar(ship.get_point());
+
+            if !inside(p) {
+                continue;
+            }
+
+            blips.push(match game.is_player(ship.id) {
+                true => Blip { point: p, r: PLAYER_SIZE, category: ship.category, kind: BlipKind::Player },
+                false => Blip { point: p, r: SHIP_SIZE, category: ship.category, kind: BlipKind::Mob },
+            });
+        }
+
+        let corner = to_radar(Point::new(visible.x, visible.y));
+
+        Radar {
+            rect: rect,
+            blips: blips,
+            viewport: Rectangle::new(corner.x, corner.y, visible.width * scale, visible.height * scale),
+        }
+    }
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum BlipKind {
+    Asteroid,
+    Mob,
+    Player,
+}
+
+// A dot on the radar, coloured by the class of ship
+#[derive(Clone,Copy,Debug)]
+pub struct Blip {
+    pub point: Point,
+    pub r: f64,
+    pub category: usize,
+    pub kind: BlipKind,
+}
+
+// What a screen draws for the minimap, all in logical pixels
+#[derive(Clone,Debug)]
+pub struct Radar {
+    pub rect: Rectangle,
+    pub blips: Vec<Blip>,
+    // What the camera shows
+    pub viewport: Rectangle,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::Game;
+    use crate::minimap::*;
+
+    #[test]
+    fn test_scan() {
+        let mut game = Game::from_json(r#"{"player": [0, 500.0, 250.0], "world": {"width": 1000.0, "height": 500.0}}"#.to_string()).unwrap();
+        game.update(&[], Point::new(0.0, 0.0), 1.0/60.0);
+        let radar = Minimap::new(Corner::BottomRight, 200.0).scan(&game, 1024.0, 768.0);
+
+        // Shaped like the world, tucked into the corner
+        assert_eq!((radar.rect.x, radar.rect.y, radar.rect.width, radar.rect.height), (814.0, 658.0, 200.0, 100.0));
+
+        let player = radar.blips.iter().find(|b| b.kind == BlipKind::Player).unwrap();
+        assert_eq!(player.point, Point::new(914.0, 708.0));
+    }
+}
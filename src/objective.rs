@@ -0,0 +1,174 @@
+use crate::game::UPS;
+use crate::physics::{Rectangle,Shape};
+use crate::storage::*;
+use crate::trigger::Situation;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Status {
+    Pending,
+    // Fine so far, but can still fail, e.g. the protected ship is alive
+    Holding,
+    Complete,
+    Failed,
+}
+
+pub struct Objective {
+    args: ObjectiveArgs,
+    status: Status,
+    children: Vec<Objective>,
+    // Line for the HUD, as of the last update
+    progress: String,
+}
+
+impl Objective {
+    pub fn new(args: &ObjectiveArgs) -> Objective {
+        let children = match args {
+            ObjectiveArgs::All(list) | ObjectiveArgs::Any(list) => list.iter().map(Objective::new).collect(),
+            _ => Vec::new(),
+        };
+
+        Objective {
+            args: args.clone(),
+            status: Status::Pending,
+            children: children,
+            progress: String::new(),
+        }
+    }
+
+    pub fn get_status(&self) -> Status {
+        self.status
+    }
+
+    // Complete and failed are final
+    pub fn update(&mut self, situation: &Situation) -> Status {
+        if self.status == Status::Complete || self.status == Status::Failed {
+            return self.status;
+        }
+
+        let time = situation.tick as f64 / UPS as f64;
+
+        let (status, progress) = match &self.args {
+            ObjectiveArgs::Survive(seconds) => (
+                match time >= *seconds { true => Status::Complete, false => Status::Pending },
+                format!("Survive {} / {}", clock(time.min(*seconds)), clock(*seconds))
+            ),
+            ObjectiveArgs::Reach(x, y, w, h) => (
+                match Rectangle::new(*x, *y, *w, *h).check_collision_point(&situation.player) {
+                    true => Status::Complete,
+                    false => Status::Pending,
+                },
+                "Reach the goal zone".to_string()
+            ),
+            ObjectiveArgs::Protect(tag) => (
+                match situation.destroyed.contains(tag) { true => Status::Failed, false => Status::Holding },
+                format!("Protect {}", tag)
+            ),
+            ObjectiveArgs::Score(score) => (
+                match situation.score >= *score { true => Status::Complete, false => Status::Pending },
+                format!("Score {} / {}", situation.score.min(*score), score)
+            ),
+            ObjectiveArgs::Destroy(tag) => (
+                match situation.destroyed.contains(tag) { true => Status::Complete, false => Status::Pending },
+                match situation.alive.get(tag) {
+                    Some(n) if *n > 1 => format!("Destroy {} ({} left)", tag, n),
+                    _ => format!("Destroy {}", tag),
+                }
+            ),
+            ObjectiveArgs::All(_) => {
+                let statuses: Vec<Status> = self.children.iter_mut().map(|c| c.update(situation)).collect();
+
+                let status = if statuses.contains(&Status::Failed) {
+                    Status::Failed
+                } else if statuses.contains(&Status::Pending) {
+                    Status::Pending
+                } else if statuses.contains(&Status::Complete) {
+                    Status::Complete
+                } else {
+                    Status::Holding
+                };
+
+                (status, "All of these:".to_string())
+            },
+            ObjectiveArgs::Any(_) => {
+                let statuses: Vec<Status> = self.children.iter_mut().map(|c| c.update(situation)).collect();
+
+                let status = if statuses.contains(&Status::Complete) {
+                    Status::Complete
+                } else if statuses.iter().all(|s| *s == Status::Failed) {
+                    Status::Failed
+                } else if statuses.contains(&Status::Holding) {
+                    Status::Holding
+                } else {
+                    Status::Pending
+                };
+
+                (status, "Any of these:".to_string())
+            },
+        };
+
+        self.status = status;
+        self.progress = progress;
+
+        status
+    }
+
+    // One line per objective, nested ones indented
+    pub fn describe(&self) -> Vec<String> {
+        let mark = match self.status {
+            Status::Complete => "[x]",
+            Status::Failed => "[!]",
+            _ => "[ ]",
+        };
+
+        let mut ret = vec![format!("{} {}", mark, self.progress)];
+
+        for child in self.children.iter() {
+            ret.extend(child.describe().iter().map(|line| format!("  {}", line)));
+        }
+
+        ret
+    }
+}
+
+fn clock(seconds: f64) -> String {
+    let seconds = seconds.floor() as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap,HashSet};
+
+    use crate::objective::*;
+    use crate::physics::Point;
+    use crate::trigger::Situation;
+
+    fn update(objective: &mut Objective, tick: u64, destroyed: &[&str]) -> Status {
+        let destroyed: HashSet<String> = destroyed.iter().map(|s| s.to_string()).collect();
+
+        objective.update(&Situation {
+            tick: tick,
+            score: 0,
+            player: Point::new(0.0, 0.0),
+            destroyed: &destroyed,
+            alive: &HashMap::new(),
+        })
+    }
+
+    #[test]
+    fn test_all_and_any() {
+        let args: ObjectiveArgs = serde_json::from_str(r#"
+            {"all": [{"survive": 10}, {"protect": "convoy"}, {"any": [{"destroy": "boss"}, {"score": 1000}]}]}
+        "#).unwrap();
+
+        let mut objective = Objective::new(&args);
+        assert_eq!(update(&mut objective, 60, &[]), Status::Pending);
+        assert_eq!(update(&mut objective, 660, &[]), Status::Pending);
+        assert_eq!(update(&mut objective, 720, &["boss"]), Status::Complete);
+        assert_eq!(objective.describe()[0], "[x] All of these:");
+
+        let mut objective = Objective::new(&args);
+        assert_eq!(update(&mut objective, 60, &["convoy"]), Status::Failed);
+        assert_eq!(update(&mut objective, 720, &["boss"]), Status::Failed);
+    }
+}
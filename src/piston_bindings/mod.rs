@@ -1,2 +1,3 @@
 pub mod wrapper;
 pub mod screen;
+pub mod store;
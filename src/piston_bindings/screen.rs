@@ -6,17 +6,24 @@ use std::cell::RefCell;
 use std::rc::Rc;
 
 use piston::input::*;
-use opengl_graphics::{GlGraphics, OpenGL};
+use opengl_graphics::{GlGraphics, GlyphCache, OpenGL};
+use graphics::{Context, Transformed};
+use graphics::math::Matrix2d;
 
+use crate::ai::{Directive,ROTATE_SCALE};
 use crate::asteroid::*;
 use crate::camera::*;
 use crate::game::*;
+use crate::minimap::*;
 use crate::physics::{Point,Vector};
 use crate::ship::*;
+use crate::widget::*;
 
 use std::f64::consts::{PI,FRAC_PI_2};
 
 const BG_COLOR: [f32; 4] = [0.6, 0.6, 0.7, 1.0];
+const HUD_COLOR: [f32; 4] = [0.25, 0.25, 0.38, 0.75];
+const FONT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
 const PALETTE: [[[f32; 4]; 2]; 3] = [
     [[0.8, 0.4, 0.4, 1.0], [0.6, 0.2, 0.2, 1.0]],
     [[0.38, 0.49, 0.2, 1.0], [0.23, 0.39, 0.03, 1.0]],
@@ -37,15 +44,18 @@ fn change_alpha(color: [f32; 4], hp: f32, category: usize) -> [f32; 4] {
 
 pub struct PistonScreen {
     args: RenderArgs,
-    offset: Point,
+    view: View,
     gl: Rc<RefCell<GlGraphics>>,
+    // Without a font, widgets are drawn without their text
+    glyphs: Option<Rc<RefCell<GlyphCache<'static>>>>,
 }
 
 impl PistonScreen {
     pub fn new(gl: Rc<RefCell<GlGraphics>>, args: RenderArgs) -> PistonScreen {
         PistonScreen {
             gl: gl,
-            offset: Point::new(0.0, 0.0),
+            view: View::new(Point::new(0.0, 0.0), 1.0, 1.0),
+            glyphs: None,
             args: RenderArgs {
                 ext_dt: 0.0,
                 width: 0,
@@ -60,6 +70,17 @@ impl PistonScreen {
         self.args = r;
     }
 
+    pub fn set_glyphs(&mut self, glyphs: Option<Rc<RefCell<GlyphCache<'static>>>>) {
+        self.glyphs = glyphs;
+    }
+
+    // Draws in the world rather than on the window
+    fn world(&self, c: &Context) -> Matrix2d {
+        c.transform
+            .zoom(self.view.get_scale())
+            .trans(-self.view.offset.x, -self.view.offset.y)
+    }
+
     pub fn clear(&self) {
         self.gl.borrow_mut().draw(self.args.viewport(), |c, gl| {
             graphics::clear(BG_COLOR, gl);
@@ -69,11 +90,7 @@ impl PistonScreen {
 
 impl Screen for PistonScreen {
     fn draw_ship(&mut self, ship: &ShipCache, time_delta: f64, tick: u64) {
-        use graphics::Transformed;
-
-        let [mut _x, mut _y, _r, _d] = ship.render_piston();
-        _x -= self.offset.x;
-        _y -= self.offset.y;
+        let [_x, _y, _r, _d] = ship.render_piston();
 
         let colors = get_palette(ship.category); //PALLETTE[ship.category as usize];
 
@@ -89,15 +106,15 @@ impl Screen for PistonScreen {
             let wing = [[0.0, -1.0], [-1.5, 0.4], [1.5, 0.4]];
             let nozzle = [[0.0, -1.0], [-0.6, 1.2], [0.6, 1.2]];
 
-            let transform = c
-                .transform
+            let world = self.world(&c);
+            let transform = world
                 .trans(_x, _y)
                 .rot_rad(_d + FRAC_PI_2)
                 .scale(_r, _r);
 
             graphics::polygon(wing_color, &nozzle, transform, gl);
             graphics::polygon(wing_color, &wing, transform, gl);
-            graphics::ellipse(ship_color, body, c.transform, gl);
+            graphics::ellipse(ship_color, body, world, gl);
 
             //graphics::line(ship_color, 1.0, [_x, _y, fx, fy], c.transform, gl);
             //graphics::line(wing_color, 1.0, [_x, _y, px, py], c.transform, gl);
@@ -107,23 +124,153 @@ impl Screen for PistonScreen {
     }
 
     fn draw_asteroid(&self, asteroid: &Asteroid) {
-        use graphics::Transformed;
-
-        let [mut _x, mut _y, _r] = asteroid.render_piston();
-        _x -= self.offset.x;
-        _y -= self.offset.y;
+        let [_x, _y, _r] = asteroid.render_piston();
 
         self.gl.borrow_mut().draw(self.args.viewport(), |c, gl| {
             let body = [_x - _r, _y - _r, _r * 2.0, _r * 2.0];
-            graphics::ellipse([0.4, 0.4, 0.4, 1.0], body, c.transform, gl);
+            graphics::ellipse([0.4, 0.4, 0.4, 1.0], body, self.world(&c), gl);
         });
     }
 
-    fn set_offset(&mut self, point: Point) {
-        self.offset = point;
+    fn set_view(&mut self, view: View) {
+        self.view = view;
     }
 
     fn draw_background(&self) {
         self.clear();
     }
+
+    fn draw_radar(&self, radar: &Radar) {
+        let r = radar.rect;
+
+        // Cut down to what fits on the radar
+        let [left, top] = [radar.viewport.x.max(r.x), radar.viewport.y.max(r.y)];
+        let right = (radar.viewport.x + radar.viewport.width).min(r.x + r.width);
+        let bottom = (radar.viewport.y + radar.viewport.height).min(r.y + r.height);
+        let v = [left, top, (right - left).max(0.0), (bottom - top).max(0.0)];
+
+        self.gl.borrow_mut().draw(self.args.viewport(), |c, gl| {
+            graphics::rectangle(HUD_COLOR, [r.x, r.y, r.width, r.height], c.transform, gl);
+
+            for blip in radar.blips.iter() {
+                let color = match blip.kind {
+                    BlipKind::Asteroid => [0.6, 0.6, 0.6, 1.0],
+                    _ => get_palette(blip.category)[0],
+                };
+                let dot = [blip.point.x - blip.r, blip.point.y - blip.r, blip.r * 2.0, blip.r * 2.0];
+
+                graphics::ellipse(color, dot, c.transform, gl);
+
+                if blip.kind == BlipKind::Player {
+                    graphics::Ellipse::new_border([1.0, 1.0, 1.0, 1.0], 0.5)
+                        .draw(dot, &c.draw_state, c.transform, gl);
+                }
+            }
+
+            graphics::Rectangle::new_border([1.0, 1.0, 1.0, 0.6], 0.5)
+                .draw(v, &c.draw_state, c.transform, gl);
+        });
+    }
+
+    fn draw_widget(&self, widget: &Widget) {
+        let elements = widget.layout_on(self.args.width as f64, self.args.height as f64);
+
+        self.gl.borrow_mut().draw(self.args.viewport(), |c, gl| {
+            for e in elements.iter() {
+                let r = e.rect;
+
+                match &e.content {
+                    Content::Panel => graphics::rectangle(HUD_COLOR, [r.x, r.y, r.width, r.height], c.transform, gl),
+                    Content::Text(text) => if let Some(glyphs) = &self.glyphs {
+                        let transform = c.transform.trans(r.x, e.baseline());
+                        graphics::text(FONT_COLOR, FONT_SIZE as u32, text, &mut *glyphs.borrow_mut(), transform, gl).ok();
+                    },
+                    Content::Bar(fill, category) => {
+                        let color = match category {
+                            Some(category) => get_palette(*category)[0],
+                            None => FONT_COLOR,
+                        };
+
+                        graphics::rectangle([0.0, 0.0, 0.0, 0.4], [r.x, r.y, r.width, r.height], c.transform, gl);
+                        graphics::rectangle(color, [r.x, r.y, r.width * fill, r.height], c.transform, gl);
+                    },
+                    Content::Icon(icon) => {
+                        let [x, y] = [r.x + r.width / 2.0, r.y + r.height / 2.0];
+                        let size = r.width.min(r.height) / 2.0;
+
+                        match *icon {
+                            Icon::Ship(category) => {
+                                let colors = get_palette(category);
+                                let s = size / 1.5;
+
+                                graphics::polygon(colors[1], &[[0.0, -1.0], [1.5, 0.4], [-1.5, 0.4]], c.transform.trans(x, y).scale(s, s), gl);
+                                graphics::ellipse(colors[0], [x - s, y - s, s * 2.0, s * 2.0], c.transform, gl);
+                            },
+                            Icon::Asteroid => graphics::ellipse([0.4, 0.4, 0.4, 1.0], [x - size, y - size, size * 2.0, size * 2.0], c.transform, gl),
+                            Icon::Star => graphics::polygon([0.93, 0.93, 0.4, 1.0], &Icon::star(), c.transform.trans(x, y).scale(size, size), gl),
+                        }
+                    },
+                }
+            }
+        });
+    }
+
+    // Without fonts, the piston overlay can only show the geometry
+    fn draw_debug(&mut self, ship: &ShipCache) {
+        let p = ship.get_point();
+        let r = ship.circle.r;
+
+        let mut lines: Vec<([f32; 4], Point)> = Vec::new();
+
+        for d in ship.actions.iter() {
+            match *d {
+                Directive::Thrust(m) => lines.push(([0.9, 0.9, 0.4, 1.0], Point::from(Vector::new(ship.direction, r * 3.0 * m)))),
+                Directive::Rotate(n) => lines.push(([0.4, 0.9, 0.9, 1.0], Point::from(Vector::new(ship.direction + n * ROTATE_SCALE, r * 2.0)))),
+                Directive::SetDirection(n) => lines.push(([0.4, 0.9, 0.4, 1.0], Point::from(Vector::new(n, r * 3.0)))),
+                Directive::Aim(target) => lines.push(([0.4, 0.9, 0.4, 1.0], target - p)),
+                Directive::Brake => lines.push(([0.9, 0.4, 0.4, 1.0], Point::from(Vector::new(ship.vector.direction + PI, r * 2.0)))),
+            }
+        }
+
+        let mut path: Vec<Point> = Vec::new();
+        let mut target: Option<Point> = None;
+        let mut perception: Option<f64> = None;
+
+        if let Some(info) = &ship.debug {
+            let colors = [[1.0, 1.0, 1.0, 1.0], [0.9, 0.1, 0.1, 1.0], [0.1, 0.1, 0.9, 1.0], [0.1, 0.9, 0.1, 1.0]];
+
+            for (i, v) in info.steering.iter().enumerate() {
+                lines.push((colors[i % colors.len()], Point::from(Vector::new(v.direction, r * 3.0))));
+            }
+
+            target = info.target;
+            path = info.path.clone();
+            perception = info.perception;
+        }
+
+        self.gl.borrow_mut().draw(self.args.viewport(), |c, gl| {
+            let world = self.world(&c);
+
+            for (color, v) in lines.iter() {
+                graphics::line(*color, 0.5, [p.x, p.y, p.x + v.x, p.y + v.y], world, gl);
+            }
+
+            if let Some(t) = target {
+                graphics::line([1.0, 0.5, 0.5, 1.0], 0.5, [p.x, p.y, t.x, t.y], world, gl);
+                graphics::Rectangle::new_border([1.0, 0.5, 0.5, 1.0], 0.5)
+                    .draw([t.x - 4.0, t.y - 4.0, 8.0, 8.0], &c.draw_state, world, gl);
+            }
+
+            let mut from = p;
+            for point in path.iter() {
+                graphics::line([0.5, 1.0, 0.5, 1.0], 0.5, [from.x, from.y, point.x, point.y], world, gl);
+                from = *point;
+            }
+
+            if let Some(range) = perception {
+                graphics::Ellipse::new_border([1.0, 1.0, 1.0, 0.25], 0.5)
+                    .draw([p.x - range, p.y - range, range * 2.0, range * 2.0], &c.draw_state, world, gl);
+            }
+        });
+    }
 }
@@ -0,0 +1,36 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::storage::Store;
+
+// One JSON file per key in a directory next to the game
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(dir: &str) -> FileStore {
+        FileStore {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+}
+
+impl Store for FileStore {
+    fn load(&self, key: &str) -> Option<String> {
+        fs::read_to_string(self.path(key)).ok()
+    }
+
+    fn save(&mut self, key: &str, value: &str) {
+        let result = fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(self.path(key), value));
+
+        if let Err(e) = result {
+            println!("Could not save {}: {}", key, e);
+        }
+    }
+}
@@ -9,49 +9,113 @@ use std::fs::File;
 use std::io::BufReader;
 use std::io::prelude::*;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use glutin_window::GlutinWindow;
-use opengl_graphics::{GlGraphics, OpenGL};
+use opengl_graphics::{GlGraphics, GlyphCache, OpenGL, TextureSettings};
 use piston::event_loop::*;
 use piston::input::*;
 use piston::window::WindowSettings;
 
+use crate::achievement::Achievements;
 use crate::broadcast::*;
+use crate::campaign::Campaign;
+use crate::difficulty::Difficulty;
 use crate::game::*;
+use crate::input::*;
+use crate::menu::*;
+use crate::minimap::*;
 use crate::piston_bindings::screen::*;
+use crate::piston_bindings::store::FileStore;
 use crate::physics::Point;
 use crate::ship::*;
+use crate::statistics::HighScores;
 use crate::storage::*;
+use crate::widget::Widget;
 
 const OPENGL_VERSION: glutin_window::OpenGL = OpenGL::V3_2;
 const BG_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
 const UPS: f64 = 60.0;
+const SAVE_DIR: &str = "save";
+const MINIMAP_SIZE: f64 = 200.0;
+// Checked in with the game, see data/font-LICENSE.txt
+const FONT: &str = "data/font.ttf";
+// How long achievements are shown
+const TOAST_TIME: Duration = Duration::from_secs(4);
 
 struct GameWrapper {
     game: Game,
-    inputs: Inputs,
-    state: GameState,
+    level: String,
+    campaign: Campaign,
+    scores: HighScores,
+    achievements: Achievements,
+    // Achievements to show, and until when
+    toasts: Vec<(String, Instant)>,
+    name: String,
+    difficulty: Option<Difficulty>,
+    mode: Mode,
+    auto_zoom: bool,
+    minimap: Option<Minimap>,
+    controls: Controls,
+    menu: Menu,
+    // Picked in the menu, for the next update to load
+    requested: Option<String>,
+    exit: bool,
+    // Of the window, for laying out the menu
+    size: Point,
     gl: Rc<RefCell<GlGraphics>>,
+    glyphs: Rc<RefCell<GlyphCache<'static>>>,
 }
 
 impl GameWrapper {
     pub fn new() -> GameWrapper {
-        let args: Vec<String> = std::env::args().collect();
-        let filename = match args.get(1) {
-            Some(s) => format!("data/{}.json", s.to_string()),
-            None => "data/game.json".to_string()
-        };
+        let args = parse_args();
+        let level = args.level.clone().unwrap_or("game".to_string());
+
+        let mut controls = Controls::from_json(&read_file("data/bindings.json"), args.players).expect("Invalid bindings.");
+
+        if args.twin_stick {
+            for player in 0..args.players {
+                controls.set_twin_stick(player, true);
+            }
+        }
+
+        let campaign = Campaign::from_json(&read_file("data/campaign.json"), Box::new(FileStore::new(SAVE_DIR)))
+            .expect("Invalid campaign.");
+
+        let mut menu = Menu::new(Box::new(FileStore::new(SAVE_DIR)), true);
+        menu.load_bindings(&mut controls);
+
+        // The command line overrides the saved settings
+        let difficulty = args.difficulty.or(menu.settings.difficulty);
+
+        // Straight into the level if there is one on the command line
+        if args.level.is_none() {
+            menu.set_campaign(&campaign);
+            menu.open(Page::Main);
+        }
 
-        println!("{}", filename);
-        let mut content = String::new();
-        let mut file = File::open(&filename).expect(&format!("File {} not found.", &filename));
-        BufReader::new(file).read_to_string(&mut content);
-        
         GameWrapper {
-            game: Game::from_json(content).expect("Invalid JSON."),
-            inputs: Inputs::new(),
-            state: GameState::Running,
+            game: load_level(&level, difficulty, args.players, args.mode, args.auto_zoom),
+            level: level,
+            campaign: campaign,
+            scores: HighScores::new(Box::new(FileStore::new(SAVE_DIR))),
+            achievements: Achievements::from_json(&read_file("data/achievements.json"), Box::new(FileStore::new(SAVE_DIR)))
+                .expect("Invalid achievements."),
+            toasts: Vec::new(),
+            minimap: args.minimap.map(|corner| Minimap::new(corner, args.minimap_size)),
+            name: args.name,
+            difficulty: difficulty,
+            mode: args.mode,
+            auto_zoom: args.auto_zoom,
+
+            controls: controls,
+            menu: menu,
+            requested: None,
+            exit: false,
+            size: Point::new(0.0, 0.0),
             gl: Rc::new(RefCell::new(GlGraphics::new(OPENGL_VERSION))),
+            glyphs: Rc::new(RefCell::new(load_font())),
         }
     }
 
@@ -63,11 +127,15 @@ impl GameWrapper {
             }
 
             if let Some(u) = e.update_args() {
-                if !self.update(&u) {
+                if !self.update(&u) && !self.next_level() {
                     break;
                 }
             }
 
+            if self.exit {
+                break;
+            }
+
             if let Some(k) = e.button_args() {
                 if k.state == ButtonState::Press {
                     self.pressed(&k.button);
@@ -81,96 +149,385 @@ impl GameWrapper {
                 self.cursor_moved(pos[0], pos[1]);
             }
 
+            if let Some(axis) = e.controller_axis_args() {
+                self.axis_moved(&axis);
+            }
+
         }
     }
 
     pub fn update(&mut self, u: &UpdateArgs) -> bool {
-        match self.state {
-            GameState::Running => {
-                self.game.update(&self.inputs.pressed, self.inputs.cursor, 1.0/UPS)
+        // Nothing to record for a level left through the menu
+        if let Some(level) = self.requested.take() {
+            self.game = load_level(&level, self.difficulty, self.controls.count_players(), self.mode, self.auto_zoom);
+            self.level = level;
+            self.achievements.start_level();
+        }
+
+        if self.menu.is_open() {
+            return true;
+        }
+
+        let running = self.game.update_players(&self.controls.tick(), 1.0/UPS);
+        self.achievements.update(&self.game);
+        self.show_achievements();
+
+        running
+    }
+
+    // Move on to whatever the campaign says comes next, if anything
+    pub fn next_level(&mut self) -> bool {
+        let stats = self.game.get_statistics();
+        let mut summary = Vec::new();
+
+        if let Some(winner) = self.game.get_winner() {
+            summary.push(format!("Player {} wins!", winner + 1));
+        }
+
+        if self.controls.count_players() > 1 {
+            for (i, (score, _)) in self.game.get_player_status().iter().enumerate() {
+                summary.push(format!("Player {}: {}", i + 1, score));
+            }
+        }
+
+        summary.append(&mut stats.summary());
+
+        if let Some(place) = self.scores.record(&self.name, &self.level, &stats) {
+            summary.push(format!("New high score, #{} on {}!", place + 1, self.level));
+
+            for line in self.scores.describe(&self.level) {
+                println!("{}", line);
+            }
+        }
+
+        // Shown over the next level until it is dismissed
+        self.menu.set_summary(summary);
+
+        self.achievements.finish(&self.level, &self.game);
+        self.show_achievements();
+
+        match self.campaign.finish(&self.level, &self.game) {
+            Some(level) => {
+                self.game = load_level(&level, self.difficulty, self.controls.count_players(), self.mode, self.auto_zoom);
+                self.level = level;
+                self.achievements.start_level();
+                self.open_menu(Page::Summary);
+                true
+            },
+            None => {
+                println!("You finished the campaign!");
+                false
             },
-            GameState::Paused => true,
         }
     }
 
     pub fn pause(&mut self) {
-        match self.state {
-            GameState::Running => {self.state = GameState::Paused},
-            GameState::Paused => {self.state = GameState::Running},
+        match self.menu.is_open() {
+            true => self.menu.close(),
+            false => self.open_menu(Page::Pause),
+        }
+    }
+
+    fn open_menu(&mut self, page: Page) {
+        self.menu.set_campaign(&self.campaign);
+        self.menu.open(page);
+    }
+
+    fn choose(&mut self, choice: Option<Choice>) {
+        match choice {
+            Some(Choice::Resume) => self.menu.close(),
+            Some(Choice::Play(level)) => {
+                self.menu.close();
+                self.requested = Some(level);
+            },
+            Some(Choice::Restart) => {
+                self.menu.close();
+                self.requested = Some(self.level.to_string());
+            },
+            Some(Choice::Quit) => self.open_menu(Page::Main),
+            Some(Choice::Exit) => self.exit = true,
+            Some(Choice::Difficulty(d)) => {
+                self.difficulty = d;
+
+                if let Some(d) = d {
+                    self.game.set_difficulty(d);
+                }
+            },
+            None => (),
         }
     }
 
     pub fn render(&mut self, r: RenderArgs) {
         let mut screen = PistonScreen::new(self.gl.clone(), r);
         screen.set_args(r);
-        
+        screen.set_glyphs(Some(self.glyphs.clone()));
+
+        // The graphics context already works in window coordinates, which
+        // is also what the cursor comes in
+        self.game.resize(r.width as f64, r.height as f64, 1.0);
+        self.size = Point::new(r.width as f64, r.height as f64);
         self.game.render(&mut screen);
+
+        if let Some(minimap) = &self.minimap {
+            screen.draw_radar(&minimap.scan(&self.game, r.width as f64, r.height as f64));
+        }
+
+        let now = Instant::now();
+        self.toasts.retain(|t| t.1 > now);
+
+        let toasts: Vec<String> = self.toasts.iter().map(|t| t.0.to_string()).collect();
+        let time = (self.game.get_broadcast().tick as f64 / UPS) as u32;
+
+        for widget in Widget::hud(&self.game, time, &toasts, self.controls.count_players()) {
+            screen.draw_widget(&widget);
+        }
+
+        if self.menu.is_open() {
+            screen.draw_widget(&self.menu.get_widget());
+        }
     }
 
     pub fn pressed(&mut self, btn: &Button) {
-        match btn {
-            &Button::Keyboard(Key::Up) | &Button::Keyboard(Key::W) => self.inputs.press('T'),
-            &Button::Keyboard(Key::Down) | &Button::Keyboard(Key::S) => self.inputs.press('B'),
-            &Button::Keyboard(Key::Left) | &Button::Keyboard(Key::A) => self.inputs.press('L'),
-            &Button::Keyboard(Key::Right) | &Button::Keyboard(Key::D) => self.inputs.press('R'),
-            &Button::Keyboard(Key::P) => {self.pause()},
-            &Button::Mouse(MouseButton::Left) => self.inputs.press('M'),
+        if self.menu.is_open() {
+            let choice = match btn {
+                Button::Controller(b) => match standard_button(b.button) {
+                    Some(button) => self.menu.button_pressed(button, &self.controls),
+                    None => None,
+                },
+                Button::Mouse(MouseButton::Left) => self.menu.clicked(self.controls.get_cursor(), self.size.x, self.size.y),
+                _ => match button_name(btn) {
+                    Some(name) => self.menu.key_pressed(&name, &mut self.controls),
+                    None => None,
+                },
+            };
+
+            return self.choose(choice);
+        }
+
+        let action = match btn {
+            &Button::Controller(b) => standard_button(b.button).and_then(|button| self.controls.button_pressed(b.id as u32, button)),
+            _ => button_name(btn).and_then(|name| self.controls.key_pressed(&name)),
+        };
+
+        match action {
+            Some(Action::Pause) => self.pause(),
+            Some(Action::Debug) => self.game.toggle_debug(),
+            Some(Action::ZoomIn) => self.game.zoom_in(),
+            Some(Action::ZoomOut) => self.game.zoom_out(),
             _ => (),
         }
     }
 
     pub fn cursor_moved(&mut self, x: f64, y: f64) {
-        self.inputs.move_cursor(x, y);
+        self.controls.move_cursor(x, y);
+
+        if self.menu.is_open() {
+            self.menu.cursor_moved(Point::new(x, y), self.size.x, self.size.y);
+        }
     }
 
     pub fn released(&mut self, btn: &Button) {
         match btn {
-            &Button::Keyboard(Key::Up) | &Button::Keyboard(Key::W) => self.inputs.release('T'),
-            &Button::Keyboard(Key::Down) | &Button::Keyboard(Key::S) => self.inputs.release('B'),
-            &Button::Keyboard(Key::Left) | &Button::Keyboard(Key::A) => self.inputs.release('L'),
-            &Button::Keyboard(Key::Right) | &Button::Keyboard(Key::D) => self.inputs.release('R'),
-            &Button::Mouse(MouseButton::Left) => self.inputs.release('M'),
-            _ => (),
+            &Button::Controller(b) => if let Some(button) = standard_button(b.button) {
+                self.controls.button_released(b.id as u32, button);
+            },
+            _ => if let Some(name) = button_name(btn) {
+                self.controls.key_released(&name);
+            },
         }
     }
-}
 
-pub struct Inputs {
-    pub pressed: Vec<char>,
-    pub cursor: Point,
-}
+    pub fn axis_moved(&mut self, axis: &ControllerAxisArgs) {
+        let gamepad = axis.id as u32;
 
-impl Inputs {
-    pub fn new() -> Inputs {
-        Inputs {
-            pressed: Vec::new(),
-            cursor: Point::new(0.0, 0.0)
+        match standard_axis(axis.axis, axis.position) {
+            Some(PadAxis::Trigger(button, value)) => self.controls.trigger_moved(gamepad, button, value),
+            Some(PadAxis::Stick(n, value)) if self.menu.is_open() => {
+                let choice = self.menu.axis_moved(gamepad, n, value, &mut self.controls);
+                self.choose(choice);
+            },
+            Some(PadAxis::Stick(n, value)) => self.controls.axis_moved(gamepad, n, value),
+            None => (),
         }
     }
 
-    pub fn press(&mut self, button: char) {
-        if !self.pressed.iter().any(|&b| b == button) {
-            self.pressed.push(button);
+    fn show_achievements(&mut self) {
+        for name in self.achievements.take_unlocked() {
+            println!("Achievement unlocked: {}", name);
+            self.toasts.push((name, Instant::now() + TOAST_TIME));
         }
     }
+}
+
+// Piston passes on the pad's own numbers, which for the usual XInput pads
+// differ from the browser's standard layout that the bindings file uses.
+// Triggers come as axes resting at -1, where the standard has buttons
+// going from 0 to 1.
+enum PadAxis {
+    Stick(u32, f64),
+    Trigger(u32, f64),
+}
 
-    pub fn release(&mut self, released: char) {
-        self.pressed.iter()
-            .position(|&b| b == released)
-            .map(|i| self.pressed.remove(i));
+fn standard_button(button: u8) -> Option<u32> {
+    match button {
+        // A, B, X, Y and the shoulders are the same
+        0..=5 => Some(button as u32),
+        // Back, start, the stick clicks and the guide button
+        6 => Some(8),
+        7 => Some(9),
+        9 => Some(10),
+        10 => Some(11),
+        8 => Some(16),
+        _ => None,
     }
+}
+
+fn standard_axis(axis: u8, position: f64) -> Option<PadAxis> {
+    match axis {
+        0 | 1 => Some(PadAxis::Stick(axis as u32, position)),
+        3 | 4 => Some(PadAxis::Stick(axis as u32 - 1, position)),
+        2 => Some(PadAxis::Trigger(6, (position + 1.0) / 2.0)),
+        5 => Some(PadAxis::Trigger(7, (position + 1.0) / 2.0)),
+        _ => None,
+    }
+}
 
-    pub fn move_cursor(&mut self, x: f64, y: f64) {
-        self.cursor = Point::new(x, y);
+// Keys and mouse buttons by the names the browser gives them, which is
+// what the bindings file uses
+fn button_name(btn: &Button) -> Option<String> {
+    match btn {
+        Button::Keyboard(Key::Up) => Some("arrowup".to_string()),
+        Button::Keyboard(Key::Down) => Some("arrowdown".to_string()),
+        Button::Keyboard(Key::Left) => Some("arrowleft".to_string()),
+        Button::Keyboard(Key::Right) => Some("arrowright".to_string()),
+        Button::Keyboard(Key::Backquote) => Some("`".to_string()),
+        Button::Keyboard(Key::Space) => Some(" ".to_string()),
+        Button::Keyboard(Key::Return) => Some("enter".to_string()),
+        Button::Keyboard(Key::Equals) => Some("=".to_string()),
+        Button::Keyboard(Key::Minus) => Some("-".to_string()),
+        Button::Keyboard(Key::NumPadPlus) => Some("+".to_string()),
+        Button::Keyboard(Key::NumPadMinus) => Some("-".to_string()),
+        Button::Keyboard(key) => Some(format!("{:?}", key).to_lowercase()),
+        Button::Mouse(button) => Some(format!("mouse{:?}", button).to_lowercase()),
+        _ => None,
     }
 }
 
+fn read_file(filename: &str) -> String {
+    let mut content = String::new();
+    let mut file = File::open(filename).unwrap_or_else(|_| panic!("File {} not found.", filename));
+    BufReader::new(file).read_to_string(&mut content);
+
+    content
+}
+
+// The HUD and the menu are no use without text
+fn load_font() -> GlyphCache<'static> {
+    GlyphCache::new(FONT, (), TextureSettings::new())
+        .unwrap_or_else(|e| panic!("Could not load the font {}: {}", FONT, e))
+}
+
+fn load_level(level: &str, difficulty: Option<Difficulty>, players: usize, mode: Mode, auto_zoom: bool) -> Game {
+    let filename = format!("data/{}.json", level);
+    println!("{}", filename);
+
+    let mut game = Game::from_json(read_file(&filename)).expect("Invalid JSON.");
+
+    // The command line overrides the level file
+    if let Some(d) = difficulty {
+        game.set_difficulty(d);
+    }
+
+    game.set_players(players, mode);
+    game.set_auto_zoom(auto_zoom);
+
+    game
+}
+
+struct Args {
+    level: Option<String>,
+    difficulty: Option<Difficulty>,
+    name: String,
+    players: usize,
+    mode: Mode,
+    twin_stick: bool,
+    auto_zoom: bool,
+    minimap: Option<Corner>,
+    minimap_size: f64,
+}
+
+// wamsbin [level] [--difficulty easy|normal|hard] [--name player] [--players 1-4] [--mode coop|versus] [--twin-stick] [--no-auto-zoom] [--minimap topleft|topright|bottomleft|bottomright|off] [--minimap-size pixels]
+fn parse_args() -> Args {
+    let mut ret = Args {
+        level: None,
+        difficulty: None,
+        name: std::env::var("USER").unwrap_or("Player".to_string()),
+        players: 1,
+        mode: Mode::Coop,
+        twin_stick: false,
+        auto_zoom: true,
+        minimap: Some(Corner::BottomLeft),
+        minimap_size: MINIMAP_SIZE,
+    };
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--difficulty" | "-d" => {
+                ret.difficulty = args.next().and_then(|d| d.parse::<Difficulty>().ok());
+
+                if ret.difficulty.is_none() {
+                    println!("Unknown difficulty, expected easy, normal or hard.");
+                }
+            },
+            "--name" | "-n" => {
+                if let Some(n) = args.next() {
+                    ret.name = n;
+                }
+            },
+            "--players" => {
+                match args.next().and_then(|n| n.parse::<usize>().ok()) {
+                    Some(n) if (1..=MAX_PLAYERS).contains(&n) => ret.players = n,
+                    _ => println!("Expected between 1 and {} players.", MAX_PLAYERS),
+                }
+            },
+            "--mode" | "-m" => {
+                match args.next().and_then(|m| m.parse::<Mode>().ok()) {
+                    Some(m) => ret.mode = m,
+                    None => println!("Unknown mode, expected coop or versus."),
+                }
+            },
+            "--twin-stick" => ret.twin_stick = true,
+            "--no-auto-zoom" => ret.auto_zoom = false,
+            "--minimap" => {
+                match args.next() {
+                    Some(ref c) if c == "off" => ret.minimap = None,
+                    Some(c) => match c.parse::<Corner>() {
+                        Ok(corner) => ret.minimap = Some(corner),
+                        Err(_) => println!("Unknown corner, expected topleft, topright, bottomleft, bottomright or off."),
+                    },
+                    None => println!("Expected a corner for the minimap."),
+                }
+            },
+            "--minimap-size" => {
+                match args.next().and_then(|n| n.parse::<f64>().ok()) {
+                    Some(n) if n > 0.0 => ret.minimap_size = n,
+                    _ => println!("Expected the size of the minimap in pixels."),
+                }
+            },
+            _ => ret.level = Some(arg),
+        }
+    }
+
+    ret
+}
+
 pub fn main() {
     let mut window: GlutinWindow = WindowSettings::new(
         "Well-Adjusted, Mature Spaceships",
         [1024, 768])
         .opengl(OPENGL_VERSION)
-        .exit_on_esc(true)
+        .exit_on_esc(false)
         .build()
         .unwrap();
 
@@ -178,8 +535,3 @@ pub fn main() {
 
     println!("Thank you for playing!");
 }
-
-pub enum GameState {
-    Running,
-    Paused,
-}
\ No newline at end of file
@@ -0,0 +1,134 @@
+use crate::physics::Point;
+use crate::ship::{Attribution,DamageSource};
+
+// Points for killing each category of ship
+pub const POINTS: [u32; 4] = [0, 100, 300, 0];
+
+// Ticks between kills to keep a combo going
+const COMBO_WINDOW: u64 = 120;
+const MAX_MULTIPLIER: f64 = 3.0;
+// Speed of the player for a ram to count as high speed
+const RAM_SPEED: f64 = 300.0;
+// Share of health the player has to be under for a close call
+const CLOSE_CALL: f64 = 0.25;
+
+// Points given, and where to show them
+#[derive(Clone,Debug,PartialEq)]
+pub struct ScoreEvent {
+    pub points: u32,
+    pub position: Point,
+    pub label: String,
+}
+
+#[derive(Default)]
+pub struct Scoring {
+    combo: u32,
+    last_kill: Option<u64>,
+    // Kills so far this tick
+    tick_kills: u32,
+}
+
+impl Scoring {
+    pub fn new() -> Scoring {
+        Scoring::default()
+    }
+
+    pub fn get_combo(&self) -> u32 {
+        self.combo
+    }
+
+    // Everything a kill by the player is worth, with the difficulty and
+    // health of the player (0 to 1) taken into account
+    pub fn score_kill(&mut self, category: usize, position: Point, tick: u64, attribution: &Attribution, health: f64, scale: f64) -> Vec<ScoreEvent> {
+        let mut ret = Vec::new();
+
+        if self.last_kill == Some(tick) {
+            self.tick_kills += 1;
+        } else {
+            self.tick_kills = 1;
+        }
+
+        self.combo = match self.last_kill {
+            Some(last) if tick - last <= COMBO_WINDOW => self.combo + 1,
+            _ => 1,
+        };
+        self.last_kill = Some(tick);
+
+        let (multiplier, label) = match attribution.blow {
+            Some(DamageSource::Asteroid(_)) => (1.5, "Bounce!"),
+            // Pushed into another ship by the player
+            _ if attribution.chain.len() > 1 => (1.5, "Pileup!"),
+            Some(DamageSource::Ship(_)) if attribution.speed >= RAM_SPEED => (2.0, "Ram!"),
+            _ => (1.0, ""),
+        };
+
+        let combo = (1.0 + (self.combo - 1) as f64 * 0.5).min(MAX_MULTIPLIER);
+        let base = *POINTS.get(category).unwrap_or(&0) as f64;
+
+        let label = match self.combo {
+            1 => label.to_string(),
+            n => format!("{} x{} combo", label, n).trim().to_string(),
+        };
+
+        ret.push(ScoreEvent {
+            points: (base * multiplier * combo * scale).round() as u32,
+            position: position,
+            label: label,
+        });
+
+        // Style bonuses
+        if self.tick_kills > 1 {
+            ret.push(ScoreEvent {
+                points: (100.0 * scale).round() as u32,
+                position: position,
+                label: "Multi kill".to_string(),
+            });
+        }
+
+        if health < CLOSE_CALL {
+            ret.push(ScoreEvent {
+                points: (50.0 * scale).round() as u32,
+                position: position,
+                label: "Close call".to_string(),
+            });
+        }
+
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::physics::Point;
+    use crate::scoring::*;
+    use crate::ship::{Attribution,DamageSource};
+
+    fn hit(speed: f64, blow: DamageSource) -> Attribution {
+        Attribution {
+            chain: vec![1],
+            speed: speed,
+            blow: Some(blow),
+            ..Attribution::default()
+        }
+    }
+
+    #[test]
+    fn test_combos() {
+        let mut scoring = Scoring::new();
+        let p = Point::new(0.0, 0.0);
+
+        assert_eq!(scoring.score_kill(1, p, 10, &hit(100.0, DamageSource::Ship(1)), 1.0, 1.0)[0].points, 100);
+
+        // Rammed at speed, within the combo window
+        let events = scoring.score_kill(1, p, 100, &hit(400.0, DamageSource::Ship(1)), 1.0, 1.0);
+        assert_eq!(events[0].points, 300);
+        assert_eq!(events[0].label, "Ram! x2 combo");
+
+        // Same tick, bounced, on its last legs
+        let events = scoring.score_kill(2, p, 100, &hit(100.0, DamageSource::Asteroid(p)), 0.1, 1.0);
+        assert_eq!(events.iter().map(|e| e.points).collect::<Vec<u32>>(), vec![900, 100, 50]);
+
+        // Too late for the combo
+        assert_eq!(scoring.score_kill(1, p, 400, &hit(100.0, DamageSource::Ship(1)), 1.0, 0.5)[0].points, 50);
+    }
+}
@@ -20,6 +20,10 @@ pub const HEALTH: [f64; 4] = [100.0, 25.0, 200.0, 80.0];
 pub const FORCE: [f64; 4] = [80.0, 24.0, 16.0, 24.0];
 pub const MASS: [f64; 4] = [1.0, 0.8, 1.2, 0.4];
 
+// Ticks a hit by another ship is remembered for
+const ATTRIBUTION_WINDOW: u64 = 180;
+const MAX_CHAIN: usize = 8;
+
 #[derive(Debug)]
 pub struct Ship {
     id: u32,
@@ -33,6 +37,10 @@ pub struct Ship {
     mass: f64,
     elasticity: f64,
     actions: Vec<Directive>,
+    debug: Option<DebugInfo>,
+    attribution: Attribution,
+    // Health lost to each ship at the end of a blame chain
+    damage: HashMap<u32, f64>,
 }
 
 impl Ship {
@@ -48,6 +56,23 @@ impl Ship {
         self.circle.get_y()
     }
 
+    pub fn get_category(&self) -> usize {
+        self.category
+    }
+
+    pub fn get_health(&self) -> f64 {
+        self.health
+    }
+
+    // How much of our health is on this ship, see blame
+    pub fn get_damage_by(&self, id: u32) -> f64 {
+        self.damage.get(&id).cloned().unwrap_or(0.0)
+    }
+
+    pub fn set_brain(&mut self, brain: Box<dyn Brain>) {
+        self.brain = brain;
+    }
+
     pub fn set_direction(&mut self, d: f64) {
         self.direction = d;
     }
@@ -99,8 +124,6 @@ impl Ship {
             self.vector.get_dx() * time_delta,
             self.vector.get_dy() * time_delta
         );
-
-        println!("{:}", time_delta);
     }
 
     pub fn rotate(&mut self, d: f64) {
@@ -114,53 +137,101 @@ impl Ship {
     pub fn check_collisions(&mut self, time_delta: f64, cast: &Broadcast, actors: &HashMap<u32, ShipCache>, props: &Vec<Asteroid>) -> bool {
         let mut collision = false;
         let trajectory = self.get_trajectory_bounds(time_delta);
+        let me = Point::new(self.circle.x, self.circle.y);
 
         for (id, actor) in actors.iter() {
+            // Ships by the other edge of a wrapping world are right next to us
+            let circle = cast.nearest_circle(me, actor.circle);
+            let mut other = actor.trajectory;
+            other.x += circle.x - actor.circle.x;
+            other.y += circle.y - actor.circle.y;
+
             if *id != self.id &&
-                trajectory.check_collision_rectangle(&actor.trajectory) &&
-                self.circle.check_collision_circle(&actor.circle) {
+                trajectory.check_collision_rectangle(&other) &&
+                self.circle.check_collision_circle(&circle) {
                     collision = true;
 
                     //println!("Ship #{:} has {:.2} HP left.", self.id, self.health as f32 / 100.0);
-                    self.collision_bounce(actor.circle, actor.vector, actor.elasticity, actor.mass);
+                    let health = self.health;
+                    self.collision_bounce(circle, actor.vector, actor.elasticity, actor.mass);
 
-                    let phi = (self.circle.y - actor.circle.y).atan2(self.circle.x - actor.circle.x);
+                    let phi = (self.circle.y - circle.y).atan2(self.circle.x - circle.x);
                     let v = Vector::new(phi, self.circle.r);
-                    let p = Point::new(self.circle.x - v.get_dx(), self.circle.y - v.get_dy());
+                    let p = cast.wrap(Point::new(self.circle.x - v.get_dx(), self.circle.y - v.get_dy()));
 
                     if self.vector.magnitude > 10.0 {
                         cast.send_message(Message::new(0, self.id,
                             MessageBody::ShipCollision(actor.id, p)
                         ));
                     }
+
+                    self.blame(actor, cast);
+                    self.record_damage(health);
             }
         }
 
         for prop in props.iter() {
-            let circle = prop.get_circle();
+            let center = prop.get_circle();
+            let circle = cast.nearest_circle(me, center);
 
             if trajectory.check_collision_shape(&circle) &&
                 self.circle.check_collision_circle(&circle) {
                 collision = true;
 
                 //println!("Ship #{:} has {:.2} HP left.", self.id, self.health as f32 / 100.0);
+                let health = self.health;
                 self.collision_bounce(circle, Vector::empty(), prop.get_elasticity(), f64::powf(2.0, 63.0)-1.0);
+                // Bouncing off an asteroid is still on whoever sent us there
+                self.record_damage(health);
 
                 let phi = (self.circle.y - circle.y).atan2(self.circle.x - circle.x);
                 let v = Vector::new(phi, self.circle.r);
-                let p = Point::new(self.circle.x - v.get_dx(), self.circle.y - v.get_dy());
+                let p = cast.wrap(Point::new(self.circle.x - v.get_dx(), self.circle.y - v.get_dy()));
 
                 if self.vector.magnitude > 10.0 {
                     cast.send_message(Message::new(0, self.id,
-                        MessageBody::AsteroidCollision(Point::new(circle.x, circle.y), p)
+                        MessageBody::AsteroidCollision(Point::new(center.x, center.y), p)
                     ));
                 }
+
+                self.attribution.bounces.push(Point::new(center.x, center.y));
+                self.attribution.blow = Some(DamageSource::Asteroid(Point::new(center.x, center.y)));
             }
         }
 
         collision
     }
 
+    // Whoever hit us is to blame, along with whoever hit them, unless that
+    // was a player, who is always the end of the line
+    fn blame(&mut self, actor: &ShipCache, cast: &Broadcast) {
+        let mut chain = vec![actor.id];
+
+        if !cast.is_player(actor.id) {
+            for id in actor.chain.iter() {
+                if *id != self.id && !chain.contains(id) {
+                    chain.push(*id);
+                }
+            }
+        }
+
+        chain.truncate(MAX_CHAIN);
+
+        self.attribution = Attribution {
+            chain: chain,
+            tick: cast.tick,
+            speed: actor.vector.magnitude.abs(),
+            bounces: Vec::new(),
+            blow: Some(DamageSource::Ship(actor.id)),
+        };
+    }
+
+    fn record_damage(&mut self, before: f64) {
+        if let Some(id) = self.attribution.get_killer() {
+            *self.damage.entry(id).or_insert(0.0) += before.max(0.0) - self.health.max(0.0);
+        }
+    }
+
     pub fn collision_bounce(&mut self, circle: Circle, vector: Vector, elasticity: f64, mass: f64) {
         let dx = self.circle.get_x() - circle.get_x();
         let dy = self.circle.get_y() - circle.get_y();
@@ -195,34 +266,56 @@ impl Ship {
         // Store state before collisions
         let alive = self.health > 0.0;
 
+        if cast.tick > self.attribution.tick + ATTRIBUTION_WINDOW {
+            self.attribution = Attribution::default();
+        }
+
         self.check_collisions(time_delta, cast, actors, props);
         self.abide_physics(time_delta);
 
+        if let Some(world) = &cast.world {
+            if world.confine(&mut self.circle, &mut self.vector) {
+                self.vector.magnitude *= self.elasticity;
+            }
+        }
+
         if self.health <= 0.0 {
             // If we were alive before collisions, notify the rest of our death
             if alive {
-                cast.send_message(Message::new(0, self.id, MessageBody::Death));
+                cast.send_message(Message::new(0, self.id, MessageBody::Death(self.attribution.clone())));
             }
 
             // Clear all actions
             self.actions = Vec::new();
+            self.debug = None;
 
             return ();
         }
 
         let actions = self.brain.think(time_delta, cast, actors, props);
 
+        // Difficulty only affects the mobs
+        let force = match cast.is_player(self.id) {
+            true => 1.0,
+            false => cast.difficulty.force(),
+        };
+
         for d in &actions {
             match d {
                 Directive::SetDirection(n) => self.direction = *n,
                 Directive::Rotate(n) => self.rotate(*n),
-                Directive::Thrust(n) => self.thrust(*n, time_delta),
+                Directive::Thrust(n) => self.thrust(*n * force, time_delta),
                 Directive::Brake => self.brake(time_delta),
-                Directive::Aim(p) => self.aim(*p),
+                // The short way round, in a wrapping world
+                Directive::Aim(p) => self.aim(cast.nearest(Point::new(self.circle.x, self.circle.y), *p)),
             }
         }
 
         self.actions = actions;
+        self.debug = match cast.debug {
+            true => self.brain.debug_info(),
+            false => None,
+        };
     }
 
     pub fn get_cache(&self, time_delta: f64) -> ShipCache {
@@ -238,6 +331,8 @@ impl Ship {
             elasticity: self.elasticity,
             trajectory: self.get_trajectory_bounds(time_delta),
             actions: self.actions.to_vec(),
+            debug: self.debug.clone(),
+            chain: self.attribution.chain.to_vec(),
         }
     }
 }
@@ -251,12 +346,42 @@ impl std::fmt::Display for Ship {
     }
 }
 
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum DamageSource {
+    Ship(u32),
+    // Center of the asteroid
+    Asteroid(Point),
+}
+
+// Who killed a ship, sent along with its death
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct Attribution {
+    // Ships to blame, from the one that hit last to whoever set it all off
+    pub chain: Vec<u32>,
+    // When the last ship hit, and how fast it was going
+    pub tick: u64,
+    pub speed: f64,
+    // Asteroids bounced off since then
+    pub bounces: Vec<Point>,
+    // What hurt the ship last
+    pub blow: Option<DamageSource>,
+}
+
+impl Attribution {
+    pub fn get_killer(&self) -> Option<u32> {
+        self.chain.last().cloned()
+    }
+}
+
 #[derive(Clone,Debug)]
 pub struct ShipBuilder {
     id: u32,
     category: usize,
     pos: Point,
     vector: Vector,
+    brain: Option<BrainArgs>,
+    label: Option<String>,
+    ally: bool,
 }
 
 impl ShipBuilder {
@@ -270,6 +395,9 @@ impl ShipBuilder {
             category: category,
             pos: Point::new(0.0, 0.0),
             vector: Vector::empty(),
+            brain: None,
+            label: None,
+            ally: false,
         }
     }
 
@@ -288,13 +416,48 @@ impl ShipBuilder {
         self
     }
 
+    pub fn brain(mut self, brain: BrainArgs) -> Self {
+        self.brain = Some(brain);
+        self
+    }
+
+    // The tag from the level file, not to be confused with the id set by tag()
+    pub fn label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    pub fn ally(mut self) -> Self {
+        self.ally = true;
+        self
+    }
+
+    pub fn get_category(&self) -> usize {
+        self.category
+    }
+
+    pub fn is_ally(&self) -> bool {
+        self.ally
+    }
+
+    pub fn get_label(&self) -> Option<String> {
+        self.label.clone()
+    }
+
+    pub fn has_brain(&self) -> bool {
+        self.brain.is_some()
+    }
+
     pub fn build(self) -> Ship {
         let cat = self.category as usize;
 
         Ship {
             id: self.id,
             category: self.category,
-            brain: build_brain(self.category, self.id),
+            brain: match &self.brain {
+                Some(brain) => brain.build(self.id),
+                None => build_brain(self.category, self.id),
+            },
             vector: self.vector,
             circle: Circle::new(self.pos.x, self.pos.y, RADIUS[cat]),
             health: HEALTH[cat],
@@ -303,6 +466,9 @@ impl ShipBuilder {
             mass: MASS[cat],
             elasticity: 2.0/3.0,
             actions: Vec::new(),
+            debug: None,
+            attribution: Attribution::default(),
+            damage: HashMap::new(),
         }
     }
 }
@@ -314,6 +480,9 @@ impl From<&ShipArgs> for ShipBuilder {
             category: s.0,
             pos: Point::new(s.1, s.2),
             vector: Vector::from(s.3),
+            brain: None,
+            label: s.5.tag.clone(),
+            ally: s.5.ally,
         }
     }
 }
@@ -357,6 +526,7 @@ impl ShipFactory {
     }
 }
 
+#[derive(Clone)]
 pub struct ShipCache {
     pub id: u32,
     pub category: usize,
@@ -369,6 +539,9 @@ pub struct ShipCache {
     pub elasticity: f64,
     pub trajectory: Rectangle,
     pub actions: Vec<Directive>,
+    pub debug: Option<DebugInfo>,
+    // Who is to blame for the ship's last hit, see Attribution
+    pub chain: Vec<u32>,
 }
 
 impl ShipCache {
@@ -376,6 +549,20 @@ impl ShipCache {
         Point::new(self.circle.x, self.circle.y)
     }
 
+    // The same ship somewhere else, e.g. drawn across the edge of a
+    // wrapping world
+    pub fn moved_to(&self, circle: Circle) -> ShipCache {
+        let mut trajectory = self.trajectory;
+        trajectory.x += circle.x - self.circle.x;
+        trajectory.y += circle.y - self.circle.y;
+
+        ShipCache {
+            circle: circle,
+            trajectory: trajectory,
+            ..self.clone()
+        }
+    }
+
     pub fn render_piston(&self) -> [f64; 4] {
         [self.circle.get_x(), self.circle.get_y(), self.circle.get_r(), self.direction]
     }
@@ -435,4 +622,44 @@ mod tests {
         println!("Case #1: Moving and still ship:");
         collide(Vector::new(std::f64::consts::FRAC_PI_2, 90.0), Vector::empty());
     }
+
+    #[test]
+    fn test_wrap_collision() {
+        use crate::world::{Edge,World};
+
+        let mut a = ShipBuilder::new(1).place(5.0, 250.0).tag(1).build();
+        let b = ShipBuilder::new(1).place(995.0, 250.0).tag(2).build();
+
+        let mut actors = HashMap::new();
+        actors.insert(1, a.get_cache(1.0/60.0));
+        actors.insert(2, b.get_cache(1.0/60.0));
+
+        // Right next to each other, across the edge
+        let mut cast = Broadcast::new();
+        assert!(!a.check_collisions(1.0/60.0, &cast, &actors, &vec![]));
+
+        cast.world = Some(World::new(1000.0, 500.0, Edge::Wrap));
+        assert!(a.check_collisions(1.0/60.0, &cast, &actors, &vec![]));
+    }
+
+    #[test]
+    fn test_blamed_damage() {
+        let mut a = ShipBuilder::new(1).place(0.0, 0.0).tag(3).build();
+        let b = ShipBuilder::new(1).place(30.0, 0.0).set_vector(Vector::new(PI, 300.0)).tag(2).build();
+
+        // The player sent b our way
+        let mut cache = b.get_cache(1.0/60.0);
+        cache.chain = vec![1];
+
+        let mut actors = HashMap::new();
+        actors.insert(2, cache);
+
+        let cast = Broadcast::new();
+        assert!(a.check_collisions(1.0/60.0, &cast, &actors, &vec![]));
+
+        let lost = HEALTH[1] - a.get_health();
+        assert!(lost > 0.0);
+        assert_eq!(a.get_damage_by(1), lost);
+        assert_eq!(a.get_damage_by(2), 0.0);
+    }
 }
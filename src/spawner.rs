@@ -1,39 +1,194 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
 use crate::broadcast::*;
-use crate::physics::{Circle,Vector};
+use crate::physics::{Point,Rectangle,Shape,Vector};
 use crate::ship::*;
+use crate::storage::*;
 
 use std::f64::consts::{TAU};
 
-pub struct ShipSpawner {
-    active: bool,
+pub struct WaveSpawner {
+    waves: Vec<WaveArgs>,
+    // The wave waiting for its trigger, or spawning
+    current: usize,
+    // Tick the current wave was triggered
+    triggered: Option<u64>,
+    // Ships of the current wave spawned so far
+    spawned: u32,
+    finished: bool,
+    rng: StdRng,
 }
 
-impl ShipSpawner {
-    pub fn new(a: bool) -> ShipSpawner {
-        ShipSpawner {
-            active: a,
+impl WaveSpawner {
+    pub fn new(waves: Vec<WaveArgs>) -> WaveSpawner {
+        WaveSpawner {
+            finished: waves.is_empty(),
+            waves: waves,
+            current: 0,
+            triggered: None,
+            spawned: 0,
+            rng: StdRng::seed_from_u64(0),
         }
     }
 
-    pub fn act(&self, cast: &Broadcast) {
-        if self.active && cast.tick % 360 == 0 {
-            self.create_ship(cast);
-        }
+    // What the old spawner did: one ship every 360 ticks, and every third is a Cayenne
+    pub fn endless() -> WaveSpawner {
+        WaveSpawner::new(vec![WaveArgs {
+            ships: vec![(JALAPENO, 2), (CAYENNE, 1)],
+            spawn: SpawnArgs::default(),
+            trigger: TriggerArgs::Time(0),
+            delay: 360,
+            interval: 360,
+            repeat: true,
+            last: false,
+        }])
+    }
+
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    // Has the final wave been spawned? Levels without one are won once the
+    // last wave is out, or a repeating wave has started, since those never run out.
+    pub fn is_finished(&self) -> bool {
+        self.finished
     }
 
-    fn create_ship(&self, cast: &Broadcast) {
-        let m = 600.0;
-        let d = (cast.tick as f64 / 360.0 ) % TAU;
-        let v = Vector::new(d, m);
+    pub fn act(&mut self, cast: &Broadcast, alive: usize) {
+        let wave = match self.waves.get(self.current) {
+            Some(wave) => wave.clone(),
+            None => return,
+        };
+
+        let triggered = match self.triggered {
+            Some(tick) => tick,
+            None => {
+                if !self.check_trigger(&wave.trigger, cast, alive) {
+                    return;
+                }
+
+                self.triggered = Some(cast.tick);
+                cast.tick
+            },
+        };
+
+        let scale = cast.difficulty.spawn_delay();
+        let delay = (wave.delay as f64 * scale) as u64;
+        let interval = ((wave.interval as f64 * scale) as u64).max(1);
+        let elapsed = cast.tick - triggered;
+
+        if elapsed < delay || !(elapsed - delay).is_multiple_of(interval) {
+            return;
+        }
+
+        let size: u32 = wave.ships.iter().map(|s| s.1).sum();
+
+        if size > 0 {
+            self.create_ship(cast, &wave, self.spawned % size);
+        }
+        self.spawned += 1;
+
+        if wave.repeat {
+            self.finished = true;
+        }
+
+        if self.spawned >= size {
+            if wave.last {
+                self.finished = true;
+            }
+
+            if wave.repeat {
+                return;
+            }
+
+            self.current += 1;
+            self.triggered = None;
+            self.spawned = 0;
 
-        let cat: usize;
-        if cast.tick % 1080 == 0 {
-            cat = 2;
-        } else {
-            cat = 1;
+            if self.current >= self.waves.len() && !self.waves.iter().any(|w| w.last) {
+                self.finished = true;
+            }
         }
-        println!("{:?}, {}", cat, cast.tick);
-        let ship = ShipBuilder::new(cat).place(v.get_dx() + 512.0, v.get_dy() + 384.0);
+    }
+
+    fn check_trigger(&self, trigger: &TriggerArgs, cast: &Broadcast, alive: usize) -> bool {
+        match *trigger {
+            TriggerArgs::Time(tick) => cast.tick >= tick,
+            TriggerArgs::Cleared => alive == 0,
+            TriggerArgs::Area(x, y, w, h) => cast.players.iter().any(|p| Rectangle::new(x, y, w, h).check_collision_point(&p.1)),
+        }
+    }
+
+    fn create_ship(&mut self, cast: &Broadcast, wave: &WaveArgs, n: u32) {
+        // Find the category of ship number n in the wave
+        let mut count = 0;
+        let category = wave.ships.iter()
+            .find(|s| {
+                count += s.1;
+                n < count
+            })
+            .map(|s| s.0)
+            .unwrap_or(JALAPENO);
+
+        let position = match &wave.spawn {
+            SpawnArgs::Circle(x, y, r) => {
+                let v = Vector::new(self.rng.gen_range(0.0, TAU), *r);
+                Point::new(x + v.get_dx(), y + v.get_dy())
+            },
+            SpawnArgs::Points(points) => match points.get(n as usize % points.len().max(1)) {
+                Some(p) => Point::from(*p),
+                None => cast.player_position,
+            },
+            SpawnArgs::Region(x, y, w, h) => Point::new(
+                x + self.rng.gen_range(0.0, w.max(1.0)),
+                y + self.rng.gen_range(0.0, h.max(1.0))
+            ),
+        };
+
+        let ship = ShipBuilder::new(category).place(position.x, position.y);
         cast.send_message(Message::new(0, 0, MessageBody::Birth(ship)));
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::broadcast::*;
+    use crate::ship::*;
+    use crate::spawner::*;
+    use crate::storage::*;
+
+    fn births(cast: &mut Broadcast) -> Vec<usize> {
+        cast.update(cast.tick);
+        cast.messages.iter()
+            .filter_map(|m| match &m.body {
+                MessageBody::Birth(ship) => Some(ship.get_category()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_waves() {
+        let waves: Vec<WaveArgs> = serde_json::from_str(r#"[
+            {"ships": [[1, 2]], "trigger": {"time": 10}, "interval": 5},
+            {"ships": [[2, 1]], "final": true}
+        ]"#).unwrap();
+
+        let mut spawner = WaveSpawner::new(waves);
+        let mut cast = Broadcast::new();
+        let mut spawned = Vec::new();
+
+        for tick in 0..30 {
+            cast.tick = tick;
+            // Pretend nothing survives
+            spawner.act(&cast, 0);
+            spawned.push(births(&mut cast));
+        }
+
+        assert_eq!(spawned[10], vec![JALAPENO]);
+        assert_eq!(spawned[15], vec![JALAPENO]);
+        assert_eq!(spawned[16], vec![CAYENNE]);
+        assert_eq!(spawned.iter().flatten().count(), 3);
+        assert!(spawner.is_finished());
+    }
+}
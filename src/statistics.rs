@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use serde::{Serialize,Deserialize};
+
+use crate::storage::*;
+
+const SAVE_KEY: &str = "highscores";
+const TABLE_SIZE: usize = 10;
+const NAMES: [&str; 4] = ["Bell", "Jalapeno", "Cayenne", "Chickpea"];
+
+// What happened during a level, from the player's point of view
+#[derive(Clone,Debug,Default)]
+pub struct Statistics {
+    // Mobs killed by the player, by category
+    pub kills: HashMap<usize, u32>,
+    // Mobs that died without the player's help
+    pub environmental_kills: u32,
+    pub damage_dealt: f64,
+    pub damage_taken: f64,
+    // Collisions of the player, and how many of them hit a ship
+    pub collisions: u32,
+    pub hits: u32,
+    pub top_speed: f64,
+    // Seconds
+    pub time: f64,
+    pub score: u32,
+}
+
+impl Statistics {
+    pub fn record_kill(&mut self, category: usize) {
+        *self.kills.entry(category).or_insert(0) += 1;
+    }
+
+    pub fn record_environmental_kill(&mut self) {
+        self.environmental_kills += 1;
+    }
+
+    pub fn record_collision(&mut self, hit: bool) {
+        self.collisions += 1;
+
+        if hit {
+            self.hits += 1;
+        }
+    }
+
+    pub fn record_speed(&mut self, speed: f64) {
+        self.top_speed = self.top_speed.max(speed);
+    }
+
+    pub fn get_kills(&self) -> u32 {
+        self.kills.values().sum()
+    }
+
+    // Share of collisions that were with ships rather than asteroids
+    pub fn get_accuracy(&self) -> f64 {
+        match self.collisions {
+            0 => 0.0,
+            n => self.hits as f64 / n as f64,
+        }
+    }
+
+    pub fn summary(&self) -> Vec<String> {
+        let mut ret = vec![
+            format!("Score: {}", self.score),
+            format!("Time: {}:{:02}", self.time as u64 / 60, self.time as u64 % 60),
+        ];
+
+        let mut categories: Vec<&usize> = self.kills.keys().collect();
+        categories.sort();
+
+        for category in categories {
+            let name = NAMES.get(*category).unwrap_or(&"Unknown");
+            ret.push(format!("{} kills: {}", name, self.kills[category]));
+        }
+
+        if self.environmental_kills > 0 {
+            ret.push(format!("Lost on their own: {}", self.environmental_kills));
+        }
+
+        ret.push(format!("Damage dealt: {:.0}", self.damage_dealt));
+        ret.push(format!("Damage taken: {:.0}", self.damage_taken));
+        ret.push(format!("Collisions: {} ({:.0}% accuracy)", self.collisions, self.get_accuracy() * 100.0));
+        ret.push(format!("Top speed: {:.0}", self.top_speed));
+
+        ret
+    }
+}
+
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct HighScore {
+    pub name: String,
+    pub level: String,
+    pub score: u32,
+    pub time: f64,
+}
+
+pub struct HighScores {
+    entries: Vec<HighScore>,
+    store: Box<dyn Store>,
+}
+
+impl HighScores {
+    pub fn new(store: Box<dyn Store>) -> HighScores {
+        let entries = store.load(SAVE_KEY)
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+
+        HighScores {
+            entries: entries,
+            store: store,
+        }
+    }
+
+    // Returns the place on the level's table, if it made it
+    pub fn record(&mut self, name: &str, level: &str, stats: &Statistics) -> Option<usize> {
+        if stats.score == 0 {
+            return None;
+        }
+
+        self.entries.push(HighScore {
+            name: name.to_string(),
+            level: level.to_string(),
+            score: stats.score,
+            time: stats.time,
+        });
+
+        // Best first, and the faster one wins a tie
+        self.entries.sort_by(|a, b| b.score.cmp(&a.score).then(a.time.partial_cmp(&b.time).unwrap()));
+
+        // Only keep the top of every level
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        self.entries.retain(|e| {
+            let count = counts.entry(e.level.to_string()).or_insert(0);
+            *count += 1;
+            *count <= TABLE_SIZE
+        });
+
+        match serde_json::to_string(&self.entries) {
+            Ok(s) => self.store.save(SAVE_KEY, &s),
+            Err(e) => println!("Could not save high scores: {}", e),
+        }
+
+        self.top(level).iter()
+            .position(|e| e.name == name && e.score == stats.score && e.time == stats.time)
+    }
+
+    pub fn top(&self, level: &str) -> Vec<HighScore> {
+        self.entries.iter()
+            .filter(|e| e.level == level)
+            .cloned()
+            .collect()
+    }
+
+    pub fn describe(&self, level: &str) -> Vec<String> {
+        self.top(level).iter()
+            .enumerate()
+            .map(|(i, e)| format!("{:>2}. {:<12} {:>6}", i + 1, e.name, e.score))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::statistics::*;
+    use crate::storage::*;
+
+    #[test]
+    fn test_high_scores() {
+        let mut scores = HighScores::new(Box::new(MemoryStore::default()));
+        let stats = |score, time| Statistics { score: score, time: time, ..Statistics::default() };
+
+        for i in 0..12 {
+            scores.record("filler", "level1", &stats(100 + i, 60.0));
+        }
+
+        assert_eq!(scores.record("ace", "level1", &stats(500, 30.0)), Some(0));
+        assert_eq!(scores.record("slow", "level1", &stats(500, 90.0)), Some(1));
+        assert_eq!(scores.record("nobody", "level1", &stats(50, 10.0)), None);
+        assert_eq!(scores.record("nobody", "level2", &stats(50, 10.0)), Some(0));
+        assert_eq!(scores.top("level1").len(), 10);
+    }
+}
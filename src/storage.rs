@@ -1,10 +1,27 @@
+use std::collections::HashMap;
+
 use serde::{Serialize,Deserialize};
 
+use crate::ai::BrainArgs;
 use crate::asteroid::*;
+use crate::difficulty::Difficulty;
+use crate::input::{Action,Axis,MenuInput};
 use crate::ship::*;
+use crate::world::Edge;
 
-#[derive(Debug,Deserialize)]
-pub struct ShipArgs(pub usize, pub f64, pub f64, #[serde(default)] pub (f64, f64), #[serde(default)] pub f64);
+#[derive(Clone,Debug,Deserialize)]
+pub struct ShipArgs(pub usize, pub f64, pub f64, #[serde(default)] pub (f64, f64), #[serde(default)] pub f64, #[serde(default)] pub ShipOptions);
+
+#[derive(Clone,Debug,Default,Deserialize)]
+#[serde(default)]
+pub struct ShipOptions {
+    // Name of an entry in the level's "brains" section
+    pub brain: Option<String>,
+    // Lets events refer to the ship, several ships can share a tag
+    pub tag: Option<String>,
+    // On the player's side: not needed for victory, and no points for losing it
+    pub ally: bool,
+}
 
 #[derive(Debug,Deserialize)]
 pub struct AsteroidArgs(pub f64, pub f64, pub f64);
@@ -12,8 +29,302 @@ pub struct AsteroidArgs(pub f64, pub f64, pub f64);
 #[derive(Debug,Deserialize)]
 pub struct WallArgs(pub i64, pub i64, pub i64, pub i64, pub (i64, i64));
 
+#[derive(Clone,Debug,Deserialize)]
+pub struct WaveArgs {
+    // Pairs of category and count, spawned in order
+    pub ships: Vec<(usize, u32)>,
+    #[serde(default)]
+    pub spawn: SpawnArgs,
+    #[serde(default)]
+    pub trigger: TriggerArgs,
+    // Ticks from the trigger to the first ship, and between each ship
+    #[serde(default)]
+    pub delay: u64,
+    #[serde(default)]
+    pub interval: u64,
+    // Start over after the last ship, forever
+    #[serde(default)]
+    pub repeat: bool,
+    // Clearing this wave wins the level
+    #[serde(default, rename = "final")]
+    pub last: bool,
+}
+
+#[derive(Clone,Debug,Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpawnArgs {
+    // Somewhere on the edge of a circle (x, y, r)
+    Circle(f64, f64, f64),
+    // Each point in turn
+    Points(Vec<(f64, f64)>),
+    // Anywhere inside a rectangle (x, y, w, h)
+    Region(f64, f64, f64, f64),
+}
+
+impl Default for SpawnArgs {
+    fn default() -> SpawnArgs {
+        SpawnArgs::Circle(512.0, 384.0, 600.0)
+    }
+}
+
+#[derive(Clone,Debug,Default,Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerArgs {
+    // Ticks since the level started
+    Time(u64),
+    // No mobs are left alive
+    #[default]
+    Cleared,
+    // The player is inside a rectangle (x, y, w, h)
+    Area(f64, f64, f64, f64),
+}
+
+#[derive(Clone,Debug,Deserialize)]
+pub struct EventArgs {
+    pub when: ConditionArgs,
+    pub then: Vec<ActionArgs>,
+    // Fire every time the condition starts to hold, not just the first
+    #[serde(default)]
+    pub repeat: bool,
+}
+
+#[derive(Clone,Debug,Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConditionArgs {
+    // The player is inside a rectangle (x, y, w, h)
+    Enter(f64, f64, f64, f64),
+    // Every ship with this tag is dead
+    Destroyed(String),
+    // Ticks since the level started
+    Timer(u64),
+    // The score is at least this
+    Score(u32),
+}
+
+#[derive(Clone,Debug,Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActionArgs {
+    Spawn(Vec<ShipArgs>),
+    // Remove or put back a group of walls from the level's "gates" section
+    Open(String),
+    Close(String),
+    // Shown on the HUD for a few seconds
+    Message(String),
+    Victory,
+    Defeat,
+}
+
+#[derive(Clone,Debug,Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ObjectiveArgs {
+    // Seconds to stay alive
+    Survive(f64),
+    // Get the player inside a rectangle (x, y, w, h)
+    Reach(f64, f64, f64, f64),
+    // Fails if every ship with this tag is destroyed
+    Protect(String),
+    Score(u32),
+    // Every ship with this tag
+    Destroy(String),
+    All(Vec<ObjectiveArgs>),
+    Any(Vec<ObjectiveArgs>),
+}
+
+#[derive(Debug,Deserialize)]
+pub struct CampaignArgs {
+    // The first level, always unlocked
+    pub start: String,
+    pub levels: HashMap<String, CampaignLevelArgs>,
+}
+
+#[derive(Clone,Debug,Default,Deserialize)]
+#[serde(default)]
+pub struct CampaignLevelArgs {
+    // Where winning leads, the campaign is over if there is nowhere
+    pub next: Option<String>,
+    // More levels to unlock on winning, besides the next one
+    pub unlocks: Vec<String>,
+    pub retry: RetryArgs,
+}
+
+#[derive(Clone,Debug,Default,Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RetryArgs {
+    // Losing restarts the same level
+    #[default]
+    Level,
+    // Losing starts the campaign over
+    Start,
+    Goto(String),
+}
+
+#[derive(Clone,Debug,Deserialize)]
+pub struct AchievementArgs {
+    pub name: String,
+    pub description: String,
+    // Everything has to happen in the same level
+    pub when: Vec<MilestoneArgs>,
+}
+
+#[derive(Clone,Debug,Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MilestoneArgs {
+    // Win the named level, or any level
+    Win(Option<String>),
+    // Take no more than this much damage over the level
+    Damage(f64),
+    Score(u32),
+    Combo(u32),
+    TopSpeed(f64),
+    // Kill this many ships within some ticks of each other
+    Kills(KillsArgs),
+}
+
+#[derive(Clone,Debug,Deserialize)]
+pub struct KillsArgs {
+    pub count: usize,
+    // Any category if none
+    #[serde(default)]
+    pub category: Option<usize>,
+    #[serde(default)]
+    pub within: u64,
+    // Only count ships that died bouncing off an asteroid
+    #[serde(default)]
+    pub bounce: bool,
+}
+
+// How big the level is and what happens at its edges
+#[derive(Clone,Debug,Deserialize)]
+pub struct WorldArgs {
+    pub width: f64,
+    pub height: f64,
+    #[serde(default)]
+    pub edge: Edge,
+}
+
+// Everything in a level file, as read by Game::from_json
+#[derive(Debug)]
+pub struct LevelArgs {
+    pub player: ShipArgs,
+    // Where players after the first start out
+    pub spawns: Vec<ShipArgs>,
+    pub mobs: Vec<ShipArgs>,
+    pub asteroids: Vec<AsteroidArgs>,
+    pub walls: Vec<WallArgs>,
+    pub gates: HashMap<String, Vec<WallArgs>>,
+    pub events: Vec<EventArgs>,
+    pub objective: Option<ObjectiveArgs>,
+    pub brains: HashMap<String, BrainArgs>,
+    // Names of brains for every ship of a category
+    pub classes: HashMap<usize, String>,
+    pub difficulty: Difficulty,
+    pub waves: Vec<WaveArgs>,
+    // Ignore the waves and keep sending ships, as levels did before waves
+    pub endless: bool,
+    pub next: String,
+    pub camera_follow: (bool, bool),
+    pub world: Option<WorldArgs>,
+}
+
+impl LevelArgs {
+    // Every name the level uses for a brain, each of which has to be in brains
+    pub fn get_brain_names(&self) -> Vec<&str> {
+        let spawned = self.events.iter()
+            .flat_map(|e| e.then.iter())
+            .flat_map(|a| match a {
+                ActionArgs::Spawn(ships) => ships.iter().collect(),
+                _ => Vec::new(),
+            });
+
+        self.mobs.iter().chain(spawned)
+            .filter_map(|s| s.5.brain.as_deref())
+            .chain(self.classes.values().map(|n| n.as_str()))
+            .collect()
+    }
+}
+
+// Names of keys are lowercase, as in the browser's KeyboardEvent.key, and
+// gamepad buttons and axes are numbered as in the standard gamepad layout
+#[derive(Clone,Debug,Default,Deserialize)]
+#[serde(default)]
+pub struct BindingsArgs {
+    // Not tied to a player, e.g. pause
+    pub keys: HashMap<String, Action>,
+    pub players: Vec<PlayerBindingsArgs>,
+    // Shared by every player's gamepad
+    pub buttons: HashMap<u32, Action>,
+    // Buttons read by how far down they are
+    pub triggers: HashMap<u32, AxisBindingArgs>,
+    pub axes: HashMap<u32, AxisBindingArgs>,
+    // Replaces axes for players in twin-stick mode
+    pub twin_stick: HashMap<u32, AxisBindingArgs>,
+    pub menu: MenuBindingsArgs,
+}
+
+#[derive(Clone,Debug,Default,Deserialize)]
+#[serde(default)]
+pub struct PlayerBindingsArgs {
+    pub keys: HashMap<String, Action>,
+    pub gamepad: Option<u32>,
+    pub twin_stick: bool,
+}
+
+// For every keyboard and gamepad alike
+#[derive(Clone,Debug,Default,Deserialize)]
+#[serde(default)]
+pub struct MenuBindingsArgs {
+    pub keys: HashMap<String, MenuInput>,
+    pub buttons: HashMap<u32, MenuInput>,
+    // What pushing a stick either way does, negative first
+    pub axes: HashMap<u32, (MenuInput, MenuInput)>,
+}
+
+#[derive(Clone,Debug,Deserialize)]
+pub struct AxisBindingArgs {
+    pub axis: Axis,
+    #[serde(default)]
+    pub invert: bool,
+}
+
+// Somewhere to keep things between runs, e.g. files or the browser's localStorage
+pub trait Store {
+    fn load(&self, key: &str) -> Option<String>;
+    fn save(&mut self, key: &str, value: &str);
+}
+
+// Forgets everything when dropped, for headless runs and tests
+#[derive(Debug,Default)]
+pub struct MemoryStore {
+    values: HashMap<String, String>,
+}
+
+impl Store for MemoryStore {
+    fn load(&self, key: &str) -> Option<String> {
+        self.values.get(key).cloned()
+    }
+
+    fn save(&mut self, key: &str, value: &str) {
+        self.values.insert(key.to_string(), value.to_string());
+    }
+}
+
 #[derive(Debug,Deserialize)]
-pub struct SpawnerArgs();
+pub struct TournamentArgs {
+    pub arenas: Vec<String>,
+    pub entrants: HashMap<String, BrainArgs>,
+    #[serde(default = "default_matches")]
+    pub matches: u64,
+    #[serde(default = "default_max_ticks")]
+    pub max_ticks: u64,
+}
+
+fn default_matches() -> u64 {
+    10
+}
+
+fn default_max_ticks() -> u64 {
+    60 * 180
+}
 
 enum ConstructorArgs {
 
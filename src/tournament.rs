@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::fs;
+
+use serde::de::Error;
+
+use crate::ai::BrainArgs;
+use crate::game::*;
+use crate::physics::Point;
+use crate::storage::*;
+
+const TIME_DELTA: f64 = 1.0 / 60.0;
+const INITIAL_RATING: f64 = 1000.0;
+const K_FACTOR: f64 = 32.0;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Outcome {
+    Player,
+    Mobs,
+    Draw,
+}
+
+#[derive(Clone,Debug)]
+pub struct MatchResult {
+    pub outcome: Outcome,
+    pub ticks: u64,
+    pub player_damage: f64,
+    pub mob_damage: f64,
+}
+
+#[derive(Clone,Debug,Default)]
+pub struct Record {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    // Ticks survived while playing as the player ship
+    pub survival: u64,
+    pub player_matches: u32,
+    pub damage_dealt: f64,
+    pub rating: f64,
+}
+
+impl Record {
+    pub fn matches(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    pub fn win_rate(&self) -> f64 {
+        self.wins as f64 / (self.matches() as f64).max(1.0)
+    }
+
+    pub fn average_survival(&self) -> f64 {
+        self.survival as f64 * TIME_DELTA / (self.player_matches as f64).max(1.0)
+    }
+
+    pub fn average_damage(&self) -> f64 {
+        self.damage_dealt / (self.matches() as f64).max(1.0)
+    }
+}
+
+// Every entrant plays every other, once as the player ship and once as all
+// the mobs, in every arena and with every seed. Builtin brains only play the
+// mobs, since they go after the nearest player, which would be themselves.
+pub struct Tournament {
+    args: TournamentArgs,
+    levels: HashMap<String, String>,
+    pub records: HashMap<String, Record>,
+}
+
+impl Tournament {
+    pub fn new(args: TournamentArgs, levels: HashMap<String, String>) -> Tournament {
+        let records = args.entrants.keys()
+            .map(|name| (name.to_string(), Record { rating: INITIAL_RATING, ..Record::default() }))
+            .collect();
+
+        Tournament {
+            args: args,
+            levels: levels,
+            records: records,
+        }
+    }
+
+    pub fn from_json(s: &str) -> serde_json::Result<Tournament> {
+        let args: TournamentArgs = serde_json::from_str(s)?;
+
+        let levels = args.arenas.iter()
+            .map(|arena| {
+                let filename = format!("data/{}.json", arena);
+                let content = fs::read_to_string(&filename)
+                    .map_err(|e| serde_json::Error::custom(format!("{}: {}", filename, e)))?;
+                Ok((arena.to_string(), content))
+            })
+            .collect::<serde_json::Result<HashMap<String, String>>>()?;
+
+        Ok(Tournament::new(args, levels))
+    }
+
+    pub fn run(&mut self) -> serde_json::Result<()> {
+        let mut names: Vec<String> = self.args.entrants.keys().cloned().collect();
+        names.sort();
+
+        let players: Vec<String> = names.iter()
+            .filter(|n| plays_player(&self.args.entrants[*n]))
+            .cloned()
+            .collect();
+        let arenas = self.args.arenas.clone();
+
+        for arena in arenas.iter() {
+            for player in players.iter() {
+                for mobs in names.iter().filter(|n| *n != player) {
+                    for seed in 0..self.args.matches {
+                        let result = self.play(arena, player, mobs, seed)?;
+                        self.record(player, mobs, &result);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn play(&self, arena: &str, player: &str, mobs: &str, seed: u64) -> serde_json::Result<MatchResult> {
+        let mut game = Game::from_json(self.levels[arena].to_string())?;
+        game.seed(seed);
+        game.set_player_brain(self.args.entrants[player].build(game.get_player_id()));
+        game.set_mob_brains(&self.args.entrants[mobs]);
+
+        while game.get_tick() < self.args.max_ticks && game.update(&[], Point::new(0.0, 0.0), TIME_DELTA) {}
+
+        Ok(MatchResult {
+            outcome: match game.get_victory() {
+                Some(true) => Outcome::Player,
+                Some(false) => Outcome::Mobs,
+                None => Outcome::Draw,
+            },
+            ticks: game.get_tick(),
+            player_damage: game.get_player_damage(),
+            mob_damage: game.get_mob_damage(),
+        })
+    }
+
+    fn record(&mut self, player: &str, mobs: &str, result: &MatchResult) {
+        let score = match result.outcome {
+            Outcome::Player => 1.0,
+            Outcome::Mobs => 0.0,
+            Outcome::Draw => 0.5,
+        };
+
+        let [a, b] = [self.records[player].rating, self.records[mobs].rating];
+        let (a, b) = elo(a, b, score);
+
+        let p = self.records.get_mut(player).unwrap();
+        p.rating = a;
+        p.player_matches += 1;
+        p.survival += result.ticks;
+        p.damage_dealt += result.player_damage;
+        match result.outcome {
+            Outcome::Player => p.wins += 1,
+            Outcome::Mobs => p.losses += 1,
+            Outcome::Draw => p.draws += 1,
+        }
+
+        let m = self.records.get_mut(mobs).unwrap();
+        m.rating = b;
+        m.damage_dealt += result.mob_damage;
+        match result.outcome {
+            Outcome::Player => m.losses += 1,
+            Outcome::Mobs => m.wins += 1,
+            Outcome::Draw => m.draws += 1,
+        }
+    }
+
+    pub fn report(&self) -> String {
+        let mut records: Vec<(&String, &Record)> = self.records.iter().collect();
+        records.sort_by(|a, b| b.1.rating.partial_cmp(&a.1.rating).unwrap());
+
+        let mut ret = format!("{:<16} {:>7} {:>6} {:>6} {:>6} {:>9} {:>10} {:>8}\n",
+            "Brain", "Rating", "Wins", "Losses", "Draws", "Win rate", "Survival", "Damage");
+
+        for (name, r) in records {
+            ret += &format!("{:<16} {:>7.0} {:>6} {:>6} {:>6} {:>8.1}% {:>9.1}s {:>8.1}\n",
+                name, r.rating, r.wins, r.losses, r.draws, r.win_rate() * 100.0,
+                r.average_survival(), r.average_damage());
+        }
+
+        ret
+    }
+}
+
+// Scripts and networks can steer whichever ship they are given
+fn plays_player(brain: &BrainArgs) -> bool {
+    match brain {
+        BrainArgs::Script(_) | BrainArgs::Network(_) => true,
+        BrainArgs::Builtin(_) | BrainArgs::Escort => false,
+    }
+}
+
+// New ratings for a and b, where score is 1.0 if a won, 0.0 if b won and 0.5 for draws
+pub fn elo(a: f64, b: f64, score: f64) -> (f64, f64) {
+    let expected = 1.0 / (1.0 + 10f64.powf((b - a) / 400.0));
+    let delta = K_FACTOR * (score - expected);
+
+    (a + delta, b - delta)
+}
+
+pub fn main(filename: &str) {
+    let result = fs::read_to_string(filename)
+        .map_err(|e| serde_json::Error::custom(format!("{}: {}", filename, e)))
+        .and_then(|content| Tournament::from_json(&content))
+        .and_then(|mut tournament| tournament.run().map(|_| tournament));
+
+    match result {
+        Ok(tournament) => println!("{}", tournament.report()),
+        Err(e) => println!("Could not run the tournament: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::script::Script;
+    use crate::tournament::*;
+
+    #[test]
+    fn test_elo() {
+        assert_eq!(elo(1000.0, 1000.0, 0.5), (1000.0, 1000.0));
+        assert_eq!(elo(1000.0, 1000.0, 1.0), (1016.0, 984.0));
+
+        // Beating a much better opponent is worth more than beating an equal
+        let (a, _) = elo(1000.0, 1400.0, 1.0);
+        assert!(a - 1000.0 > 16.0);
+    }
+
+    #[test]
+    fn test_missing_arena() {
+        let s = r#"{"arenas": ["nowhere"], "entrants": {}}"#;
+        assert!(Tournament::from_json(s).is_err());
+    }
+
+    #[test]
+    fn test_plays_player() {
+        assert!(plays_player(&BrainArgs::Script(Script::compile("fn think() { [] }").unwrap())));
+        assert!(!plays_player(&BrainArgs::Builtin(1)));
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::{HashMap,HashSet};
+
+use crate::physics::{Point,Rectangle,Shape};
+use crate::storage::*;
+
+// What the conditions of a level's events are checked against
+pub struct Situation<'a> {
+    pub tick: u64,
+    pub score: u32,
+    pub player: Point,
+    // Tags with no ship left alive
+    pub destroyed: &'a HashSet<String>,
+    // Living ships for every tag
+    pub alive: &'a HashMap<String, usize>,
+}
+
+pub struct Triggers {
+    events: Vec<EventArgs>,
+    // Whether each condition held last tick, events fire when it starts to
+    active: Vec<bool>,
+    fired: Vec<bool>,
+}
+
+impl Triggers {
+    pub fn new(events: Vec<EventArgs>) -> Triggers {
+        Triggers {
+            active: vec![false; events.len()],
+            fired: vec![false; events.len()],
+            events: events,
+        }
+    }
+
+    // Actions of every event that fires this tick, in the order of the level file
+    pub fn check(&mut self, situation: &Situation) -> Vec<ActionArgs> {
+        let mut ret = Vec::new();
+
+        for (i, event) in self.events.iter().enumerate() {
+            let active = check_condition(&event.when, situation);
+
+            if active && !self.active[i] && (event.repeat || !self.fired[i]) {
+                self.fired[i] = true;
+                ret.extend(event.then.iter().cloned());
+            }
+
+            self.active[i] = active;
+        }
+
+        ret
+    }
+}
+
+fn check_condition(condition: &ConditionArgs, situation: &Situation) -> bool {
+    match condition {
+        ConditionArgs::Enter(x, y, w, h) => Rectangle::new(*x, *y, *w, *h).check_collision_point(&situation.player),
+        ConditionArgs::Destroyed(tag) => situation.destroyed.contains(tag),
+        ConditionArgs::Timer(tick) => situation.tick >= *tick,
+        ConditionArgs::Score(score) => situation.score >= *score,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap,HashSet};
+
+    use crate::physics::Point;
+    use crate::storage::*;
+    use crate::trigger::*;
+
+    #[test]
+    fn test_triggers() {
+        let events: Vec<EventArgs> = serde_json::from_str(r#"[
+            {"when": {"enter": [0, 0, 100, 100]}, "then": [{"open": "door"}], "repeat": true},
+            {"when": {"destroyed": "boss"}, "then": ["victory"]},
+            {"when": {"timer": 5}, "then": [{"message": "Hurry up!"}]}
+        ]"#).unwrap();
+
+        let mut triggers = Triggers::new(events);
+        let mut destroyed = HashSet::new();
+        let mut fired = Vec::new();
+
+        for tick in 0..20 {
+            if tick == 12 {
+                destroyed.insert("boss".to_string());
+            }
+
+            // The player passes through the area twice
+            let x = match tick % 8 < 4 { true => 50.0, false => 500.0 };
+
+            fired.push(triggers.check(&Situation {
+                tick: tick,
+                score: 0,
+                player: Point::new(x, 50.0),
+                destroyed: &destroyed,
+                alive: &HashMap::new(),
+            }).len());
+        }
+
+        assert_eq!(fired[0], 1);
+        assert_eq!(fired[5], 1);
+        assert_eq!(fired[8], 1);
+        assert_eq!(fired[12], 1);
+        assert_eq!(fired[16], 1);
+        assert_eq!(fired.iter().sum::<usize>(), 5);
+    }
+}
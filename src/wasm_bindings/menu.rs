@@ -1,5 +0,0 @@
-pub struct Menu {
-    /*
-    ADD THINGS HERE IN THE FUTURE
-    */
-}
\ No newline at end of file
@@ -1,5 +1,4 @@
-pub mod menu;
 pub mod particle;
 pub mod screen;
-pub mod widget;
-pub mod wrapper;
\ No newline at end of file
+pub mod store;
+pub mod wrapper;
@@ -75,3 +75,33 @@ impl Particle {
         Particle::new_collision(p.x, p.y, 12.0, "#ffdd66aa")
     }
 }
+
+// Floating text, e.g. points for a kill
+pub struct Popup {
+    pub x: f64,
+    pub y: f64,
+    pub text: String,
+    pub elapsed: f64,
+    pub lifetime: f64,
+}
+
+impl Popup {
+    pub fn new(x: f64, y: f64, text: String) -> Popup {
+        Popup {
+            x: x,
+            y: y,
+            text: text,
+            elapsed: 0.0,
+            lifetime: 1.5,
+        }
+    }
+
+    pub fn tick(&mut self, time_delta: f64) {
+        self.elapsed += time_delta;
+        self.y -= 30.0 * time_delta;
+    }
+
+    pub fn get_alpha(&self) -> f64 {
+        (self.lifetime - self.elapsed) / self.lifetime
+    }
+}
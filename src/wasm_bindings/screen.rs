@@ -6,15 +6,18 @@ use crate::asteroid::*;
 use crate::broadcast::*;
 use crate::camera::*;
 use crate::game::*;
+use crate::minimap::*;
 use crate::physics::{Point,Rectangle,Shape,Vector};
 use crate::ship::*;
 use crate::wasm_bindings::*;
+use crate::widget::*;
 use crate::wasm_bindings::particle::*;
 
 
 use std::f64::consts::{PI,FRAC_PI_2};
 
 const FONT: &str = "16px Monospace";
+const DEBUG_FONT: &str = "11px Monospace";
 const FONT_COLOR: &str = "#ffffff";
 const HUD_COLOR: &str = "#404060c0";
 
@@ -45,8 +48,13 @@ fn get_alpha(hp: f64, category: usize) -> f64 {
 pub struct WasmScreen {
     ctx: web_sys::CanvasRenderingContext2d,
     particles: Vec<Particle>,
+    popups: Vec<Popup>,
+    // In logical pixels, which is what widgets are laid out in
     pub size: Point,
-    pub offset: Point,
+    // Canvas pixels per logical pixel
+    pub dpi: f64,
+    pub view: View,
+    // The part of the world in sight
     pub rect: Rectangle,
 }
 
@@ -57,68 +65,109 @@ impl WasmScreen {
         WasmScreen {
             ctx: ctx,
             size: Point::new(canvas.width().into(), canvas.height().into()),
-            offset: Point::new(0.0, 0.0),
+            dpi: 1.0,
+            view: View::new(Point::new(0.0, 0.0), 1.0, 1.0),
             particles: Vec::new(),
+            popups: Vec::new(),
             rect: Rectangle::new(0.0, 0.0, canvas.width().into(), canvas.height().into()),
         }
     }
 
-    pub fn resize(&mut self) {
+    pub fn resize(&mut self, dpi: f64) {
         let canvas = self.ctx.canvas().unwrap();
 
+        self.dpi = dpi;
         self.size = Point::new(
-            canvas.width().into(),
-            canvas.width().into()
-        );
-        self.rect = Rectangle::new(
-            0.0, 0.0,
-            canvas.width().into(),
-            canvas.height().into()
+            f64::from(canvas.width()) / dpi,
+            f64::from(canvas.height()) / dpi
         );
+    }
+
+    // Of the canvas itself, in its own pixels
+    pub fn get_canvas_size(&self) -> Point {
+        Point::new(self.size.x * self.dpi, self.size.y * self.dpi)
+    }
+
+    // From here on, draw in the world
+    fn in_world(&self) {
+        let s = self.view.get_scale();
+        self.ctx.set_transform(s, 0.0, 0.0, s, -self.view.offset.x * s, -self.view.offset.y * s).unwrap();
+    }
 
+    // From here on, draw in logical pixels
+    fn on_screen(&self) {
+        self.ctx.set_transform(self.dpi, 0.0, 0.0, self.dpi, 0.0, 0.0).unwrap();
     }
 
     pub fn clear(&self) {
         self.ctx.clear_rect(0.0, 0.0, self.size.x, self.size.y);
     }
 
-    pub fn draw_widget(&self, w: widget::Widget) {
-        self.ctx.set_fill_style(&JsValue::from(&HUD_COLOR.to_string()));
-        self.ctx.fill_rect(w.x, w.y, w.width, w.height);
-        self.ctx.set_fill_style(&JsValue::from(&FONT_COLOR.to_string()));
-        self.ctx.set_font(FONT);
-
-        for p in w.text {
-            self.ctx.fill_text(&p.body, w.x + p.x, w.y + p.y);
+    // Fitted into the rectangle, on screen
+    fn draw_icon(&self, icon: Icon, rect: Rectangle) {
+        let [x, y] = [rect.x + rect.width / 2.0, rect.y + rect.height / 2.0];
+        let r = rect.width.min(rect.height) / 2.0;
+
+        match icon {
+            Icon::Ship(category) => {
+                let colors = get_palette(category);
+                let s = r / 1.5;
+
+                self.ctx.set_fill_style(&JsValue::from(&colors[1]));
+                self.ctx.begin_path();
+                self.ctx.move_to(x, y - s);
+                self.ctx.line_to(x + 1.5 * s, y + 0.4 * s);
+                self.ctx.line_to(x - 1.5 * s, y + 0.4 * s);
+                self.ctx.fill();
+
+                self.ctx.set_fill_style(&JsValue::from(&colors[0]));
+                self.ctx.begin_path();
+                self.ctx.arc(x, y, s, 0.0, PI * 2.0).unwrap();
+                self.ctx.fill();
+            },
+            Icon::Asteroid => {
+                self.ctx.set_fill_style(&JsValue::from("#999"));
+                self.ctx.begin_path();
+                self.ctx.arc(x, y, r, 0.0, PI * 2.0).unwrap();
+                self.ctx.fill();
+            },
+            Icon::Star => {
+                self.ctx.set_fill_style(&JsValue::from("#eeee66"));
+                self.ctx.begin_path();
+
+                for p in Icon::star() {
+                    self.ctx.line_to(x + p[0] * r, y + p[1] * r);
+                }
+
+                self.ctx.fill();
+            },
         }
     }
     
     pub fn draw_offscreen_ship(&self, ship: &ShipCache) {
         let colors = get_palette(ship.category as usize);
 
+        self.on_screen();
+
+        // Where it would be, in logical pixels
+        let p = self.view.to_screen(ship.get_point());
+        let [px, py] = [p.x / self.dpi, p.y / self.dpi];
+
         // Distance from border
         let b = 30.0;
-        let x = match ship.circle.x < self.offset.x + b {
-            true => f64::max(ship.circle.x - self.offset.x, b),
-            false => f64::min(ship.circle.x - self.offset.x, self.rect.width - b)
-        };
-        let y = match ship.circle.y < self.offset.y + b {
-            true => f64::max(ship.circle.y - self.offset.y, b),
-            false => f64::min(ship.circle.y - self.offset.y, self.rect.height - b)
-        };
-
-        let center = Point::new(
-            self.offset.x + self.rect.width / 2.0,
-            self.offset.y + self.rect.height / 2.0
-        );
-        let v = Vector::from(ship.get_point() - (Point::new(x, y) + self.offset));
+        let x = px.max(b).min(self.size.x - b);
+        let y = py.max(b).min(self.size.y - b);
+
+        let v = Vector::from(Point::new(px - x, py - y));
         let d = v.direction;
+        // How far off it is in the world, rather than on screen
+        let distance = v.magnitude / self.view.zoom;
 
         //let d = v.direction;
         //let x = self.rect.width / 2.0 + d.cos() * 359.0;
         //let y = self.rect.height / 2.0 + d.sin() * 359.0;
         let r = 10.0;
-        let clamped = r * 4.0 / (1.0 + std::f64::consts::E.powf(-(v.magnitude/(self.rect.width*4.0))));
+        let clamped = r * 4.0 / (1.0 + std::f64::consts::E.powf(-(distance/(self.rect.width*4.0))));
         
         self.ctx.set_fill_style(&JsValue::from("#999"));
         self.ctx.begin_path();
@@ -143,17 +192,53 @@ impl WasmScreen {
         }
 
         self.particles.retain(|p| p.lifetime > p.elapsed);
+        self.in_world();
 
         for p in &self.particles {
             self.ctx.set_fill_style(&p.color);
             self.ctx.set_global_alpha(p.get_alpha());
             self.ctx.begin_path();
-            self.ctx.arc(p.x - self.offset.x, p.y - self.offset.y, p.get_size(), 0.0, std::f64::consts::PI * 2.0).unwrap();
+            self.ctx.arc(p.x, p.y, p.get_size(), 0.0, std::f64::consts::PI * 2.0).unwrap();
             self.ctx.fill();
             self.ctx.set_global_alpha(1.0);
         }
     }
 
+    pub fn draw_score(&mut self, cast: &Broadcast) {
+        for msg in cast.messages.iter().filter(|m| m.recipient == 0) {
+            if let MessageBody::Score(event) = &msg.body {
+                // Stack the bonuses of the same kill
+                let stacked = self.popups.iter()
+                    .filter(|p| p.elapsed == 0.0 && p.x == event.position.x)
+                    .count();
+
+                let text = match event.label.is_empty() {
+                    true => format!("+{}", event.points),
+                    false => format!("+{} {}", event.points, event.label),
+                };
+
+                self.popups.push(Popup::new(event.position.x, event.position.y - 20.0 * stacked as f64, text));
+            }
+        }
+
+        for p in self.popups.iter_mut() {
+            p.tick(1.0/60.0);
+        }
+
+        self.popups.retain(|p| p.lifetime > p.elapsed);
+        self.in_world();
+
+        self.ctx.set_fill_style(&JsValue::from(FONT_COLOR));
+        self.ctx.set_font(FONT);
+
+        for p in &self.popups {
+            self.ctx.set_global_alpha(p.get_alpha());
+            self.ctx.fill_text(&p.text, p.x, p.y);
+        }
+
+        self.ctx.set_global_alpha(1.0);
+    }
+
     pub fn draw_collision(&mut self, cast: &Broadcast) {
         cast.messages.iter()
             .filter(|m| m.recipient == 0)
@@ -170,9 +255,7 @@ impl WasmScreen {
 
 
     fn draw_euclidean_vectors(&self, ship: &ShipCache) {
-        let [mut x, mut y, r] = [ship.circle.x, ship.circle.y, ship.circle.r];
-        x -= self.offset.x;
-        y -= self.offset.y;
+        let [x, y, r] = [ship.circle.x, ship.circle.y, ship.circle.r];
 
         self.ctx.set_stroke_style(&JsValue::from(&"#000000".to_string()));
 
@@ -208,10 +291,47 @@ impl WasmScreen {
         self.ctx.stroke();
     }
 
+    fn draw_line(&self, color: &str, from: Point, to: Point) {
+        self.ctx.set_stroke_style(&JsValue::from(color));
+        self.ctx.begin_path();
+        self.ctx.move_to(from.x, from.y);
+        self.ctx.line_to(to.x, to.y);
+        self.ctx.stroke();
+    }
+
+    fn draw_directives(&self, ship: &ShipCache) {
+        let p = ship.get_point();
+        let r = ship.circle.r;
+
+        for d in ship.actions.iter() {
+            match *d {
+                Directive::Thrust(m) => {
+                    let v = Vector::new(ship.direction, r * 3.0 * m);
+                    self.draw_line("#eeee66", p, p + Point::from(v));
+                },
+                Directive::Rotate(n) => {
+                    self.ctx.set_stroke_style(&JsValue::from("#66eeee"));
+                    self.ctx.begin_path();
+                    self.ctx.arc_with_anticlockwise(p.x, p.y, r * 1.6,
+                        ship.direction, ship.direction + n * ROTATE_SCALE, n < 0.0).unwrap();
+                    self.ctx.stroke();
+                },
+                Directive::SetDirection(n) => {
+                    self.draw_line("#66ee66", p, p + Point::from(Vector::new(n, r * 3.0)));
+                },
+                Directive::Aim(target) => {
+                    self.draw_line("#66ee66", p, target);
+                },
+                Directive::Brake => {
+                    let v = Vector::new(ship.vector.direction + PI, r * 2.0);
+                    self.draw_line("#ee6666", p, p + Point::from(v));
+                },
+            }
+        }
+    }
+
     fn draw_manhattan_vectors(&self, ship: &ShipCache) {
-        let [mut x, mut y, r] = [ship.circle.x, ship.circle.y, ship.circle.r];
-        x -= self.offset.x;
-        y -= self.offset.y;
+        let [x, y, r] = [ship.circle.x, ship.circle.y, ship.circle.r];
 
         self.ctx.begin_path();
         self.ctx.move_to(x, y);
@@ -227,13 +347,10 @@ impl WasmScreen {
 
 impl Screen for WasmScreen {
     fn draw_ship(&mut self, ship: &ShipCache, time_delta: f64, tick: u64) {
-        let [mut x, mut y, r] = [ship.circle.x, ship.circle.y, ship.circle.r];
+        let [x, y, r] = [ship.circle.x, ship.circle.y, ship.circle.r];
         let colors = get_palette(ship.category as usize);
         let alpha = get_alpha(ship.health, ship.category);
 
-        let rect = Rectangle::new(self.offset.x, self.offset.y,
-            self.size.x, self.size.y);
-
         if !self.rect.check_collision_shape(&ship.circle) {
             self.draw_offscreen_ship(ship);
             return ();
@@ -252,9 +369,7 @@ impl Screen for WasmScreen {
                 Particle::new_trail(x + v.get_dx(), y + v.get_dy(), ship.vector.clone()));
         }
 
-        x -= self.offset.x;
-        y -= self.offset.y;
-
+        self.in_world();
         self.ctx.translate(x, y);
         self.ctx.rotate(ship.direction + FRAC_PI_2);
         
@@ -275,7 +390,7 @@ impl Screen for WasmScreen {
         self.ctx.line_to(-1.5 * r, 0.4 * r);
         self.ctx.fill();
 
-        self.ctx.set_transform(1.0, 0.0, 0.0, 1.0, 0.0, 0.0);
+        self.in_world();
 
         // Body
         self.ctx.set_fill_style(&JsValue::from(&colors[0]));
@@ -290,26 +405,147 @@ impl Screen for WasmScreen {
 
     fn draw_asteroid(&self, asteroid: &Asteroid) {
         let circle = asteroid.get_circle();
-        let [mut x, mut y, r] = [circle.x, circle.y, circle.r];
-        x -= self.offset.x;
-        y -= self.offset.y;
+        let [x, y, r] = [circle.x, circle.y, circle.r];
 
+        self.in_world();
         self.ctx.set_fill_style(&JsValue::from("#999"));
         self.ctx.begin_path();
         self.ctx.arc(x, y, r, 0.0, std::f64::consts::PI * 2.0).unwrap();
         self.ctx.fill();
     }
 
-    fn set_offset(&mut self, point: Point) {
-        self.offset = point;
-        self.rect.x = point.x;
-        self.rect.y = point.y;
-        //self.draw_background();
+    fn set_view(&mut self, view: View) {
+        self.view = view;
+        self.rect = view.get_visible(self.size.x * self.dpi, self.size.y * self.dpi);
     }
 
     fn draw_background(&self) {
+        self.on_screen();
         self.clear();
         self.ctx.set_fill_style(&JsValue::from("#666688".to_string()));
         self.ctx.fill_rect(0.0, 0.0, self.size.x, self.size.y);
     }
+
+    fn draw_radar(&self, radar: &Radar) {
+        self.on_screen();
+
+        let r = radar.rect;
+        self.ctx.set_fill_style(&JsValue::from(HUD_COLOR));
+        self.ctx.fill_rect(r.x, r.y, r.width, r.height);
+
+        // Nothing spills out of it
+        self.ctx.save();
+        self.ctx.begin_path();
+        self.ctx.rect(r.x, r.y, r.width, r.height);
+        self.ctx.clip();
+
+        for blip in radar.blips.iter() {
+            let color = match blip.kind {
+                BlipKind::Asteroid => "#999999".to_string(),
+                _ => get_palette(blip.category)[0].to_string(),
+            };
+
+            self.ctx.set_fill_style(&JsValue::from(&color));
+            self.ctx.begin_path();
+            self.ctx.arc(blip.point.x, blip.point.y, blip.r, 0.0, std::f64::consts::PI * 2.0).unwrap();
+            self.ctx.fill();
+
+            if blip.kind == BlipKind::Player {
+                self.ctx.set_stroke_style(&JsValue::from(FONT_COLOR));
+                self.ctx.stroke();
+            }
+        }
+
+        let v = radar.viewport;
+        self.ctx.set_stroke_style(&JsValue::from("#ffffffa0"));
+        self.ctx.stroke_rect(v.x, v.y, v.width, v.height);
+
+        self.ctx.restore();
+    }
+
+    fn draw_widget(&self, widget: &Widget) {
+        self.on_screen();
+
+        for e in widget.layout_on(self.size.x, self.size.y) {
+            let r = e.rect;
+
+            match &e.content {
+                Content::Panel => {
+                    self.ctx.set_fill_style(&JsValue::from(HUD_COLOR));
+                    self.ctx.fill_rect(r.x, r.y, r.width, r.height);
+                },
+                Content::Text(text) => {
+                    self.ctx.set_fill_style(&JsValue::from(FONT_COLOR));
+                    self.ctx.set_font(FONT);
+                    self.ctx.fill_text(text, r.x, e.baseline());
+                },
+                Content::Bar(fill, category) => {
+                    let color = match category {
+                        Some(c) => get_palette(*c)[0].to_string(),
+                        None => FONT_COLOR.to_string(),
+                    };
+
+                    self.ctx.set_fill_style(&JsValue::from("#00000060"));
+                    self.ctx.fill_rect(r.x, r.y, r.width, r.height);
+                    self.ctx.set_fill_style(&JsValue::from(&color));
+                    self.ctx.fill_rect(r.x, r.y, r.width * fill, r.height);
+                },
+                Content::Icon(icon) => self.draw_icon(*icon, r),
+            }
+        }
+    }
+
+    fn draw_debug(&mut self, ship: &ShipCache) {
+        let p = ship.get_point();
+        let [x, y, r] = [p.x, p.y, ship.circle.r];
+
+        if !self.rect.check_collision_shape(&ship.circle) {
+            return ();
+        }
+
+        self.in_world();
+
+        self.draw_euclidean_vectors(ship);
+        self.draw_directives(ship);
+
+        let mut lines = ship.actions.iter()
+            .map(|d| format!("{:.2?}", d))
+            .collect::<Vec<String>>();
+
+        if let Some(info) = &ship.debug {
+            if let Some(range) = info.perception {
+                self.ctx.set_stroke_style(&JsValue::from("#ffffff40"));
+                self.ctx.begin_path();
+                self.ctx.arc(x, y, range, 0.0, std::f64::consts::PI * 2.0).unwrap();
+                self.ctx.stroke();
+            }
+
+            if let Some(target) = info.target {
+                self.draw_line("#ff8888", p, target);
+                self.ctx.set_stroke_style(&JsValue::from("#ff8888"));
+                self.ctx.stroke_rect(target.x - 4.0, target.y - 4.0, 8.0, 8.0);
+            }
+
+            let mut from = p;
+            for point in info.path.iter() {
+                self.draw_line("#88ff88", from, *point);
+                from = *point;
+            }
+
+            for (i, v) in info.steering.iter().enumerate() {
+                let colors = ["#ffffff", "#ee2222", "#2222ee", "#22ee22"];
+                let v = Vector::new(v.direction, r * 3.0);
+                self.draw_line(colors[i % colors.len()], p, p + Point::from(v));
+            }
+
+            lines.insert(0, info.state.to_string());
+        }
+
+        self.ctx.set_fill_style(&JsValue::from(FONT_COLOR));
+        self.ctx.set_font(DEBUG_FONT);
+
+        for (i, line) in lines.iter().enumerate() {
+            self.ctx.fill_text(line, x + r * 1.5, y - r + i as f64 * 12.0);
+        }
+    }
 }
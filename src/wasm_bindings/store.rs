@@ -0,0 +1,29 @@
+use crate::storage::Store;
+
+const PREFIX: &str = "wams.";
+
+// The browser's localStorage, which only keeps strings anyway
+pub struct LocalStore {
+    storage: Option<web_sys::Storage>,
+}
+
+impl LocalStore {
+    pub fn new() -> LocalStore {
+        LocalStore {
+            // Missing when the user has turned off site data
+            storage: web_sys::window().and_then(|w| w.local_storage().ok()).flatten(),
+        }
+    }
+}
+
+impl Store for LocalStore {
+    fn load(&self, key: &str) -> Option<String> {
+        self.storage.as_ref()?.get_item(&format!("{}{}", PREFIX, key)).ok()?
+    }
+
+    fn save(&mut self, key: &str, value: &str) {
+        if let Some(storage) = &self.storage {
+            storage.set_item(&format!("{}{}", PREFIX, key), value).ok();
+        }
+    }
+}
@@ -3,17 +3,25 @@ extern crate console_error_panic_hook;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::cell::Cell;
 use std::rc::Rc;
-use std::collections::HashMap;
+use std::collections::{HashMap,HashSet};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 
+use crate::achievement::Achievements;
 use crate::broadcast::*;
+use crate::campaign::Campaign;
+use crate::difficulty::Difficulty;
 use crate::game::*;
+use crate::input::*;
+use crate::menu::*;
+use crate::minimap::*;
 use crate::physics::Point;
 use crate::ship::*;
+use crate::statistics::HighScores;
 use crate::storage::*;
-use crate::wasm_bindings::widget::*;
 use crate::wasm_bindings::screen::*;
+use crate::wasm_bindings::store::LocalStore;
+use crate::widget::Widget;
 
 #[wasm_bindgen]
 extern "C" {
@@ -21,6 +29,10 @@ extern "C" {
     fn log(a: String);
 }
 
+// How long achievements are shown, in milliseconds
+const TOAST_TIME: f64 = 4000.0;
+const MINIMAP_SIZE: f64 = 200.0;
+
 pub fn now() -> f64 {
     web_sys::window().unwrap().performance().unwrap().now()
 }
@@ -28,53 +40,187 @@ pub fn now() -> f64 {
 #[wasm_bindgen]
 pub struct GameWrapper {
     game: Game,
-    state: GameState,
+    level: String,
+    campaign: Campaign,
+    scores: HighScores,
+    achievements: Achievements,
+    // Achievements to show, and until when
+    toasts: Vec<(String, f64)>,
+    name: String,
+    menu: Menu,
+    // Picked in the menu, to be fetched by the page
+    requested: Option<String>,
     last_pause: f64,
     screen: WasmScreen,
-    inputs: Inputs,
+    minimap: Option<Minimap>,
+    controls: Controls,
+    // Gamepad buttons held as of the last update, by gamepad and button
+    buttons: HashSet<(u32, u32)>,
+    mode: Mode,
+    auto_zoom: bool,
     idle: f64,
+    difficulty: Option<Difficulty>,
 }
 
 #[wasm_bindgen]
 impl GameWrapper {
-    pub fn new(s: String, ctx: web_sys::CanvasRenderingContext2d) -> GameWrapper {
+    pub fn new(level: String, s: String, campaign: String, achievements: String, bindings: String, ctx: web_sys::CanvasRenderingContext2d) -> GameWrapper {
+        let mut controls = Controls::from_json(&bindings, 1).expect("Invalid bindings.");
+        let mut menu = Menu::new(Box::new(LocalStore::new()), false);
+        menu.load_bindings(&mut controls);
+        let campaign = Campaign::from_json(&campaign, Box::new(LocalStore::new())).expect("Invalid campaign.");
+        let achievements = Achievements::from_json(&achievements, Box::new(LocalStore::new())).expect("Invalid achievements.");
+
         match Game::from_json(s) {
-            Ok(game) => GameWrapper {
-                game: game,
-                state: GameState::Running,
-                last_pause: 0.0,
-                screen: WasmScreen::new(ctx),
-                inputs: Inputs::new(),
-                idle: now(),
+            Ok(game) => {
+                let mut wrapper = GameWrapper {
+                    game: game,
+                    level: level,
+                    campaign: campaign,
+                    scores: HighScores::new(Box::new(LocalStore::new())),
+                    achievements: achievements,
+                    toasts: Vec::new(),
+                    name: "Player".to_string(),
+                    difficulty: menu.settings.difficulty,
+                    menu: menu,
+                    requested: None,
+                    last_pause: 0.0,
+                    screen: WasmScreen::new(ctx),
+                    minimap: Some(Minimap::new(Corner::BottomLeft, MINIMAP_SIZE)),
+                    controls: controls,
+                    buttons: HashSet::new(),
+                    mode: Mode::Coop,
+                    auto_zoom: true,
+                    idle: now(),
+                };
+
+                if let Some(d) = wrapper.difficulty {
+                    wrapper.game.set_difficulty(d);
+                }
+
+                wrapper.fit_view();
+                wrapper
             },
             Err(e) => panic!(e)
         }
     }
 
+    // False when the level is over, or another one was picked in the menu
     pub fn update(&mut self) -> bool {
-        match self.state {
-            GameState::Running => {
-                self.game.update(&self.inputs.pressed, self.inputs.cursor, 1.0/60.0)
-            },
-            GameState::Paused => true,
+        self.poll_gamepads();
+
+        if self.requested.is_some() {
+            return false;
         }
+
+        if self.menu.is_open() {
+            return true;
+        }
+
+        let running = self.game.update_players(&self.controls.tick(), 1.0/60.0);
+        self.achievements.update(&self.game);
+        self.show_achievements();
+
+        running
     }
 
     pub fn pause(&mut self) {
-        match self.state {
-            GameState::Running => {
-                self.state = GameState::Paused;
-                self.last_pause = now();
-            },
-            GameState::Paused => {
-                self.state = GameState::Running;
-                self.idle += now() - self.last_pause;
+        match self.menu.is_open() {
+            true => self.close_menu(),
+            false => self.open_menu(Page::Pause),
+        }
+    }
+
+    // What the page shows first, unless it goes straight to a level
+    pub fn show_main_menu(&mut self) {
+        self.open_menu(Page::Main);
+    }
+
+    // Name to put on the high-score table
+    pub fn set_player_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    // Overrides the difficulty of this and every following level
+    pub fn set_difficulty(&mut self, difficulty: &str) {
+        match difficulty.parse::<Difficulty>() {
+            Ok(d) => {
+                self.difficulty = Some(d);
+                self.game.set_difficulty(d);
             },
+            Err(e) => log(e),
+        }
+    }
+
+    // This and every following level starts with all of them
+    pub fn set_players(&mut self, count: usize, mode: &str) {
+        let count = count.max(1).min(MAX_PLAYERS);
+
+        match mode.parse::<Mode>() {
+            Ok(m) => self.mode = m,
+            Err(e) => log(e),
+        }
+
+        self.controls.set_players(count);
+        self.game.set_players(count, self.mode);
+    }
+
+    // For this and every following level
+    pub fn set_auto_zoom(&mut self, on: bool) {
+        self.auto_zoom = on;
+        self.game.set_auto_zoom(on);
+    }
+
+    // In a corner of the screen, e.g. "topright", or "off"
+    pub fn set_minimap(&mut self, corner: &str, size: f64) {
+        self.minimap = match corner.parse::<Corner>() {
+            Ok(c) => Some(Minimap::new(c, size)),
+            Err(_) => None,
+        };
+    }
+
+    // One stick aims and the other thrusts, for every player
+    pub fn set_twin_stick(&mut self, on: bool) {
+        for player in 0..self.controls.count_players() {
+            self.controls.set_twin_stick(player, on);
         }
     }
 
-    pub fn get_successor_args(&mut self) -> String {
-        self.game.get_successor_args()
+    // Records the result with the campaign and returns the level to fetch
+    // next, or nothing if the campaign is over
+    pub fn finish_level(&mut self) -> Option<String> {
+        // Nothing to record for a level left through the menu
+        if let Some(level) = self.requested.take() {
+            self.level = level.to_string();
+            return Some(level);
+        }
+
+        let stats = self.game.get_statistics();
+        let mut summary = stats.summary();
+
+        if let Some(winner) = self.game.get_winner() {
+            summary.insert(0, format!("Player {} wins!", winner + 1));
+        }
+
+        if let Some(place) = self.scores.record(&self.name, &self.level, &stats) {
+            summary.push(format!("New high score, #{} on {}!", place + 1, self.level));
+            log(self.scores.describe(&self.level).join("\n"));
+        }
+
+        // Shown over the next level until it is dismissed
+        self.menu.set_summary(summary);
+        self.open_menu(Page::Summary);
+
+        self.achievements.finish(&self.level, &self.game);
+        self.show_achievements();
+
+        let next = self.campaign.finish(&self.level, &self.game);
+
+        if let Some(level) = &next {
+            self.level = level.to_string();
+        }
+
+        next
     }
 
     pub fn next_state(&mut self, s: String) {
@@ -82,109 +228,230 @@ impl GameWrapper {
         self.game.get_broadcast().tick, now() - self.idle, self.game.get_broadcast().tick as f64 / ((now() - self.idle) / 1000.0)));
         self.game = Game::from_json(s).unwrap();
 
-        // If we go from a state with following camera to one with locked, this must be reset.
-        self.screen.set_offset(Point::new(0.0, 0.0));
+        if let Some(d) = self.difficulty {
+            self.game.set_difficulty(d);
+        }
+
+        self.game.set_players(self.controls.count_players(), self.mode);
+        self.game.set_auto_zoom(self.auto_zoom);
 
-        // Reset staring time
+        self.fit_view();
+
+        self.achievements.start_level();
+
+        // Reset staring time, and the pause if the summary is up
         self.idle = now();
+        self.last_pause = self.idle;
     }
 
     pub fn render(&mut self) {
         self.game.render(&mut self.screen);
         self.screen.draw_collision(self.game.get_broadcast());
         self.screen.draw_particles();
+        self.screen.draw_score(self.game.get_broadcast());
 
-        match self.state {
-            GameState::Paused => {
-                self.screen.draw_widget(Widget::pause(
-                    self.screen.size.x, self.screen.size.y
-                ))
-            },
-            _ => ()
+        let time = match self.menu.is_open() {
+            false => now() - self.idle,
+            true => self.last_pause - self.idle,
         };
 
-        let time = match self.state {
-            GameState::Running => now() - self.idle,
-            GameState::Paused => self.last_pause - self.idle,
-        };
+        let clock = now();
+        self.toasts.retain(|t| t.1 > clock);
+
+        if let Some(minimap) = &self.minimap {
+            self.screen.draw_radar(&minimap.scan(&self.game, self.screen.size.x, self.screen.size.y));
+        }
+
+        let toasts: Vec<String> = self.toasts.iter().map(|t| t.0.to_string()).collect();
+
+        for widget in Widget::hud(&self.game, (time / 1000.0).floor() as u32, &toasts, self.controls.count_players()) {
+            self.screen.draw_widget(&widget);
+        }
 
-        self.screen.draw_widget(Widget::status(
-            self.game.get_score(),
-            self.game.get_player_health().ceil() as u32,
-            self.game.get_player_speed(),
-            time.floor() as u32 / 1000,
-        ));
+        if self.menu.is_open() {
+            self.screen.draw_widget(&self.menu.get_widget());
+        }
     }
 
-    pub fn resize(&mut self) {
-        self.screen.resize();
+    // After the canvas has been resized, with the ratio of its pixels to
+    // the page's
+    pub fn resize(&mut self, dpi: f64) {
+        self.screen.resize(dpi);
+        self.fit_view();
     }
 
     pub fn pressed(&mut self, btn: &str) {
-        match btn {
-            "arrowup" | "w" => self.inputs.press('T'),
-            "arrowdown" | "s" => self.inputs.press('B'),
-            "arrowleft" | "a"=> self.inputs.press('L'),
-            "arrowright" | "d"=> self.inputs.press('R'),
-            "p" => {self.pause()},
-            _ => log(format!("btn: {}", btn.to_string())),
+        if self.menu.is_open() {
+            let choice = self.menu.key_pressed(btn, &mut self.controls);
+            return self.choose(choice);
         }
+
+        let action = self.controls.key_pressed(btn);
+        self.global_action(action);
     }
 
     pub fn released(&mut self, btn: &str) {
-        match btn {
-            "arrowup" | "w" => self.inputs.release('T'),
-            "arrowdown" | "s" => self.inputs.release('B'),
-            "arrowleft" | "a" => self.inputs.release('L'),
-            "arrowright" | "d" => self.inputs.release('R'),
-            _ => (),
-        }
+        self.controls.key_released(btn);
     }
 
     pub fn mouse_pressed(&mut self) {
-        self.inputs.press('M')
+        if self.menu.is_open() {
+            let cursor = self.get_cursor();
+            let choice = self.menu.clicked(cursor, self.screen.size.x, self.screen.size.y);
+            return self.choose(choice);
+        }
+
+        self.pressed("mouseleft");
     }
 
     pub fn mouse_released(&mut self) {
-        self.inputs.release('M')
+        self.released("mouseleft");
     }
 
+    // In the page's pixels, relative to the canvas
     pub fn cursor_moved(&mut self, x: f64, y: f64) {
-        self.inputs.move_cursor(x, y);
+        self.controls.move_cursor(x * self.screen.dpi, y * self.screen.dpi);
+
+        if self.menu.is_open() {
+            let cursor = self.get_cursor();
+            self.menu.cursor_moved(cursor, self.screen.size.x, self.screen.size.y);
+        }
     }
 }
 
-pub struct Inputs {
-    pub pressed: Vec<char>,
-    pub cursor: Point,
-}
+impl GameWrapper {
+    fn fit_view(&mut self) {
+        let size = self.screen.get_canvas_size();
+        self.game.resize(size.x, size.y, self.screen.dpi);
+    }
+
+    // In logical pixels, which the menu is laid out in
+    fn get_cursor(&self) -> Point {
+        let cursor = self.controls.get_cursor();
+        Point::new(cursor.x / self.screen.dpi, cursor.y / self.screen.dpi)
+    }
+
+    fn open_menu(&mut self, page: Page) {
+        if !self.menu.is_open() {
+            self.last_pause = now();
+        }
+
+        self.menu.set_campaign(&self.campaign);
+        self.menu.open(page);
+    }
+
+    fn close_menu(&mut self) {
+        if self.menu.is_open() {
+            self.idle += now() - self.last_pause;
+        }
+
+        self.menu.close();
+    }
+
+    fn choose(&mut self, choice: Option<Choice>) {
+        match choice {
+            Some(Choice::Resume) => self.close_menu(),
+            Some(Choice::Play(level)) => {
+                self.close_menu();
+                self.requested = Some(level);
+            },
+            Some(Choice::Restart) => {
+                self.close_menu();
+                self.requested = Some(self.level.to_string());
+            },
+            Some(Choice::Quit) => self.open_menu(Page::Main),
+            Some(Choice::Difficulty(d)) => {
+                self.difficulty = d;
 
-impl Inputs {
-    pub fn new() -> Inputs {
-        Inputs {
-            pressed: Vec::new(),
-            cursor: Point::new(0.0, 0.0)
+                if let Some(d) = d {
+                    self.game.set_difficulty(d);
+                }
+            },
+            // A page can't close itself
+            Some(Choice::Exit) | None => (),
         }
     }
 
-    pub fn press(&mut self, button: char) {
-        if !self.pressed.iter().any(|&b| b == button) {
-            self.pressed.push(button);
+    fn global_action(&mut self, action: Option<Action>) {
+        match action {
+            Some(Action::Pause) => self.pause(),
+            Some(Action::Debug) => self.game.toggle_debug(),
+            Some(Action::ZoomIn) => self.game.zoom_in(),
+            Some(Action::ZoomOut) => self.game.zoom_out(),
+            _ => (),
         }
     }
 
-    pub fn release(&mut self, released: char) {
-        self.pressed.iter()
-            .position(|&b| b == released)
-            .map(|i| self.pressed.remove(i));
+    // The Gamepad API has no events for buttons, so they are read every tick
+    fn poll_gamepads(&mut self) {
+        let gamepads = match web_sys::window().unwrap().navigator().get_gamepads() {
+            Ok(gamepads) => gamepads,
+            Err(_) => return,
+        };
+
+        let mut held = HashSet::new();
+
+        for i in 0..gamepads.length() {
+            let gamepad = match gamepads.get(i).dyn_into::<web_sys::Gamepad>() {
+                Ok(gamepad) => gamepad,
+                Err(_) => continue,
+            };
+
+            let buttons = gamepad.buttons();
+            for b in 0..buttons.length() {
+                let button = match buttons.get(b).dyn_into::<web_sys::GamepadButton>() {
+                    Ok(button) => button,
+                    Err(_) => continue,
+                };
+
+                if button.pressed() {
+                    held.insert((gamepad.index(), b));
+                }
+
+                self.controls.trigger_moved(gamepad.index(), b, button.value());
+            }
+
+            let axes = gamepad.axes();
+            for a in 0..axes.length() {
+                if let Some(value) = axes.get(a).as_f64() {
+                    match self.menu.is_open() {
+                        true => {
+                            let choice = self.menu.axis_moved(gamepad.index(), a, value, &mut self.controls);
+                            self.choose(choice);
+                        },
+                        false => self.controls.axis_moved(gamepad.index(), a, value),
+                    }
+                }
+            }
+        }
+
+        let pressed: Vec<(u32, u32)> = held.difference(&self.buttons).cloned().collect();
+
+        for (gamepad, button) in pressed.iter() {
+            match self.menu.is_open() {
+                true => {
+                    let choice = self.menu.button_pressed(*button, &self.controls);
+                    self.choose(choice);
+                },
+                false => {
+                    let action = self.controls.button_pressed(*gamepad, *button);
+                    self.global_action(action);
+                },
+            }
+        }
+
+        for (gamepad, button) in self.buttons.difference(&held) {
+            self.controls.button_released(*gamepad, *button);
+        }
+
+        self.buttons = held;
     }
 
-    pub fn move_cursor(&mut self, x: f64, y: f64) {
-        self.cursor = Point::new(x, y);
+    fn show_achievements(&mut self) {
+        for name in self.achievements.take_unlocked() {
+            log(format!("Achievement unlocked: {}", name));
+            self.toasts.push((name, now() + TOAST_TIME));
+        }
     }
 }
 
-enum GameState {
-    Running,
-    Paused,
-}
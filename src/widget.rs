@@ -0,0 +1,460 @@
+use crate::game::Game;
+use crate::physics::{Point,Rectangle,Shape};
+use crate::ship::*;
+
+// Text is laid out as if every character were this wide, which is close
+// enough for the monospace fonts both screens use
+pub const FONT_SIZE: f64 = 16.0;
+pub const LINE_HEIGHT: f64 = 24.0;
+pub const CHAR_WIDTH: f64 = 9.6;
+const ICON_SIZE: f64 = 16.0;
+const BAR_WIDTH: f64 = 100.0;
+const BAR_HEIGHT: f64 = 8.0;
+// From the edges of the screen, and between stacked widgets
+const MARGIN: f64 = 10.0;
+const PADDING: f64 = 12.0;
+// Where the speed bar is full
+const SPEED_GAUGE: f64 = 600.0;
+
+// Which point of the parent a widget sticks to
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    // Where something this big goes in the parent, pushed in by the offset
+    pub fn place(&self, parent: &Rectangle, size: Point, offset: Point) -> Rectangle {
+        let x = match self {
+            Anchor::TopLeft | Anchor::Left | Anchor::BottomLeft => parent.x + offset.x,
+            Anchor::Top | Anchor::Center | Anchor::Bottom => parent.x + (parent.width - size.x) / 2.0 + offset.x,
+            Anchor::TopRight | Anchor::Right | Anchor::BottomRight => parent.x + parent.width - size.x - offset.x,
+        };
+        let y = match self {
+            Anchor::TopLeft | Anchor::Top | Anchor::TopRight => parent.y + offset.y,
+            Anchor::Left | Anchor::Center | Anchor::Right => parent.y + (parent.height - size.y) / 2.0 + offset.y,
+            Anchor::BottomLeft | Anchor::Bottom | Anchor::BottomRight => parent.y + parent.height - size.y - offset.y,
+        };
+
+        Rectangle::new(x, y, size.x, size.y)
+    }
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Size {
+    // In logical pixels
+    Fixed(f64),
+    // Of the parent, from 0 to 1
+    Relative(f64),
+    // Just big enough for what is inside
+    Fit,
+}
+
+impl Size {
+    fn resolve(&self, parent: f64, natural: f64) -> f64 {
+        match *self {
+            Size::Fixed(n) => n,
+            Size::Relative(f) => parent * f,
+            Size::Fit => natural,
+        }
+    }
+}
+
+// How children are arranged inside a widget
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Layout {
+    // Wherever their anchors put them
+    Free,
+    // One under the other, this far apart
+    Column(f64),
+    // Side by side, this far apart
+    Row(f64),
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Icon {
+    Ship(usize),
+    Asteroid,
+    Star,
+}
+
+impl Icon {
+    // Outline of a star with five points, around (0, 0) with radius 1
+    pub fn star() -> Vec<[f64; 2]> {
+        (0..10)
+            .map(|i| {
+                let r = if i % 2 == 0 { 1.0 } else { 0.45 };
+                let a = std::f64::consts::PI * (i as f64 / 5.0 - 0.5);
+                [a.cos() * r, a.sin() * r]
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub enum Content {
+    // The box behind a widget
+    Panel,
+    Text(String),
+    // How full, from 0 to 1, in the colour of a class of ship if any
+    Bar(f64, Option<usize>),
+    Icon(Icon),
+}
+
+// Something for a screen to draw, in logical pixels
+#[derive(Clone,Debug)]
+pub struct Element {
+    pub rect: Rectangle,
+    pub content: Content,
+    // Of the widget it came from, see Widget::tag
+    pub tag: Option<usize>,
+}
+
+impl Element {
+    // Where text in the element sits, as screens draw it from the baseline
+    pub fn baseline(&self) -> f64 {
+        self.rect.y + (self.rect.height + FONT_SIZE) / 2.0 - 2.0
+    }
+}
+
+#[derive(Clone,Debug)]
+pub struct Widget {
+    pub anchor: Anchor,
+    // Away from the anchor, towards the middle of the parent
+    pub offset: Point,
+    pub width: Size,
+    pub height: Size,
+    pub padding: f64,
+    pub background: bool,
+    pub content: Option<Content>,
+    pub layout: Layout,
+    pub children: Vec<Widget>,
+    // For telling which widget is under the cursor, e.g. menu items
+    pub tag: Option<usize>,
+}
+
+impl Widget {
+    pub fn new() -> Widget {
+        Widget {
+            anchor: Anchor::TopLeft,
+            offset: Point::new(0.0, 0.0),
+            width: Size::Fit,
+            height: Size::Fit,
+            padding: 0.0,
+            background: false,
+            content: None,
+            layout: Layout::Free,
+            children: Vec::new(),
+            tag: None,
+        }
+    }
+
+    pub fn text(text: &str) -> Widget {
+        Widget::new().content(Content::Text(text.to_string()))
+    }
+
+    pub fn bar(fill: f64, category: Option<usize>) -> Widget {
+        Widget::new().content(Content::Bar(fill.clamp(0.0, 1.0), category))
+    }
+
+    pub fn icon(icon: Icon) -> Widget {
+        Widget::new().content(Content::Icon(icon))
+    }
+
+    // A box of text, one line under the other
+    pub fn lines(lines: &[String]) -> Widget {
+        lines.iter().fold(
+            Widget::new().panel().layout(Layout::Column(0.0)),
+            |w, line| w.child(Widget::text(line))
+        )
+    }
+
+    pub fn anchor(mut self, anchor: Anchor, x: f64, y: f64) -> Widget {
+        self.anchor = anchor;
+        self.offset = Point::new(x, y);
+        self
+    }
+
+    pub fn size(mut self, width: Size, height: Size) -> Widget {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    // With a background and some room around what is inside
+    pub fn panel(mut self) -> Widget {
+        self.background = true;
+        self.padding = PADDING;
+        self
+    }
+
+    pub fn content(mut self, content: Content) -> Widget {
+        self.content = Some(content);
+        self
+    }
+
+    pub fn layout(mut self, layout: Layout) -> Widget {
+        self.layout = layout;
+        self
+    }
+
+    pub fn child(mut self, child: Widget) -> Widget {
+        self.children.push(child);
+        self
+    }
+
+    pub fn tag(mut self, tag: usize) -> Widget {
+        self.tag = Some(tag);
+        self
+    }
+
+    // The tag of whatever is on top at a point, in logical pixels
+    pub fn tag_at(elements: &[Element], point: Point) -> Option<usize> {
+        elements.iter()
+            .rev()
+            .filter(|e| e.tag.is_some())
+            .find(|e| e.rect.check_collision_point(&point))
+            .and_then(|e| e.tag)
+    }
+
+    // Everything to draw, back to front, on a screen this big
+    pub fn layout_on(&self, width: f64, height: f64) -> Vec<Element> {
+        let screen = Rectangle::new(0.0, 0.0, width, height);
+        let mut ret = Vec::new();
+
+        self.place(&screen, self.get_size(&screen), &mut ret);
+        ret
+    }
+
+    // What Fit comes to, with the padding
+    pub fn measure(&self) -> Point {
+        let own = match &self.content {
+            Some(Content::Text(text)) => Point::new(text.chars().count() as f64 * CHAR_WIDTH, LINE_HEIGHT),
+            Some(Content::Bar(_, _)) => Point::new(BAR_WIDTH, BAR_HEIGHT),
+            Some(Content::Icon(_)) => Point::new(ICON_SIZE, ICON_SIZE),
+            _ => Point::new(0.0, 0.0),
+        };
+
+        // Relative children take whatever room there is, so count as none
+        let sizes: Vec<Point> = self.children.iter()
+            .map(|c| {
+                let natural = c.measure();
+                Point::new(c.width.resolve(0.0, natural.x), c.height.resolve(0.0, natural.y))
+            })
+            .collect();
+
+        let gaps = sizes.len().saturating_sub(1) as f64;
+
+        let inner = match self.layout {
+            Layout::Free => self.children.iter().zip(sizes.iter())
+                .fold(Point::new(0.0, 0.0), |m, (c, s)| Point::new(m.x.max(c.offset.x + s.x), m.y.max(c.offset.y + s.y))),
+            Layout::Column(gap) => Point::new(
+                sizes.iter().fold(0.0, |m, s| m.max(s.x)),
+                sizes.iter().map(|s| s.y).sum::<f64>() + gap * gaps
+            ),
+            Layout::Row(gap) => Point::new(
+                sizes.iter().map(|s| s.x).sum::<f64>() + gap * gaps,
+                sizes.iter().fold(0.0, |m, s| m.max(s.y))
+            ),
+        };
+
+        Point::new(
+            own.x.max(inner.x) + self.padding * 2.0,
+            own.y.max(inner.y) + self.padding * 2.0
+        )
+    }
+
+    fn get_size(&self, parent: &Rectangle) -> Point {
+        let natural = self.measure();
+        Point::new(self.width.resolve(parent.width, natural.x), self.height.resolve(parent.height, natural.y))
+    }
+
+    fn place(&self, area: &Rectangle, size: Point, elements: &mut Vec<Element>) {
+        let rect = self.anchor.place(area, size, self.offset);
+        let inner = Rectangle::new(
+            rect.x + self.padding,
+            rect.y + self.padding,
+            (rect.width - self.padding * 2.0).max(0.0),
+            (rect.height - self.padding * 2.0).max(0.0)
+        );
+
+        if self.background {
+            elements.push(Element { rect: rect, content: Content::Panel, tag: self.tag });
+        }
+
+        if let Some(content) = &self.content {
+            elements.push(Element { rect: inner, content: content.clone(), tag: self.tag });
+        }
+
+        // Each child gets a slot along the column or row, and sits in it by
+        // its anchor
+        let mut next = 0.0;
+
+        for child in self.children.iter() {
+            let size = child.get_size(&inner);
+
+            let slot = match self.layout {
+                Layout::Free => inner,
+                Layout::Column(_) => Rectangle::new(inner.x, inner.y + next, inner.width, size.y),
+                Layout::Row(_) => Rectangle::new(inner.x + next, inner.y, size.x, inner.height),
+            };
+
+            next += match self.layout {
+                Layout::Free => 0.0,
+                Layout::Column(gap) => size.y + gap,
+                Layout::Row(gap) => size.x + gap,
+            };
+
+            child.place(&slot, size, elements);
+        }
+    }
+
+    // Everything on top of the game, for both screens. Time is in seconds,
+    // and toasts are the achievements to show, oldest first.
+    pub fn hud(game: &Game, time: u32, toasts: &[String], players: usize) -> Vec<Widget> {
+        let mut ret = Vec::new();
+
+        let mut left = vec![Widget::status(game.get_score(), game.get_player_health(), game.get_player_speed(), time)];
+        let objectives = game.get_objectives();
+
+        if !objectives.is_empty() {
+            left.push(Widget::objectives(&objectives));
+        }
+
+        ret.push(Widget::stack(Anchor::TopLeft, Layout::Column(MARGIN), left));
+
+        let notices = game.get_notices();
+        if !notices.is_empty() {
+            ret.push(Widget::notice(&notices));
+        }
+
+        if players > 1 {
+            let status = game.get_player_status().iter()
+                .enumerate()
+                .map(|(i, (score, health))| Widget::player(i, *score, *health))
+                .collect();
+
+            ret.push(Widget::stack(Anchor::TopRight, Layout::Row(MARGIN), status));
+        }
+
+        if !toasts.is_empty() {
+            let toasts = toasts.iter().map(|t| Widget::toast(t)).collect();
+            ret.push(Widget::stack(Anchor::BottomRight, Layout::Column(MARGIN), toasts));
+        }
+
+        ret
+    }
+
+    // Top left, for everyone
+    pub fn status(score: u32, health: f64, speed: f64, time: u32) -> Widget {
+        Widget::new().panel()
+            .anchor(Anchor::TopLeft, MARGIN, MARGIN)
+            .size(Size::Fixed(150.0), Size::Fit)
+            .layout(Layout::Column(0.0))
+            .child(Widget::new()
+                .layout(Layout::Row(8.0))
+                .child(Widget::icon(Icon::Ship(BELL)).anchor(Anchor::Left, 0.0, 0.0))
+                .child(Widget::text(&format!("{:>6}", score))))
+            .child(Widget::text(&format!("Health: {:>5}", health.ceil())))
+            .child(Widget::bar(health / HEALTH[BELL], Some(BELL)).size(Size::Relative(1.0), Size::Fit))
+            .child(Widget::text(&format!("Speed: {:>6}", speed.round())))
+            .child(Widget::bar(speed / SPEED_GAUGE, None).size(Size::Relative(1.0), Size::Fit))
+            .child(Widget::text(&format!("Time: {:>4}:{:02}", time / 60, time % 60)))
+    }
+
+    pub fn objectives(lines: &[String]) -> Widget {
+        Widget::lines(lines)
+    }
+
+    pub fn toast(text: &str) -> Widget {
+        Widget::new().panel()
+            .size(Size::Fixed(320.0), Size::Fit)
+            .layout(Layout::Column(0.0))
+            .child(Widget::new()
+                .layout(Layout::Row(8.0))
+                .child(Widget::icon(Icon::Star).anchor(Anchor::Left, 0.0, 0.0))
+                .child(Widget::text("Achievement unlocked!")))
+            .child(Widget::text(text))
+    }
+
+    // One for every player
+    pub fn player(n: usize, score: u32, health: f64) -> Widget {
+        Widget::new().panel()
+            .size(Size::Fixed(150.0), Size::Fit)
+            .layout(Layout::Column(0.0))
+            .child(Widget::text(&format!("Player {}", n + 1)))
+            .child(Widget::text(&format!("Score: {:>6}", score)))
+            .child(Widget::bar(health / HEALTH[BELL], Some(BELL)).size(Size::Relative(1.0), Size::Fit))
+    }
+
+    // Messages from level events, centered at the top
+    pub fn notice(lines: &[String]) -> Widget {
+        Widget::lines(lines)
+            .anchor(Anchor::Top, 0.0, MARGIN)
+            .size(Size::Fixed(480.0), Size::Fit)
+    }
+
+    // Several widgets one after another from a corner of the screen, the
+    // first one closest to it
+    pub fn stack(anchor: Anchor, layout: Layout, mut widgets: Vec<Widget>) -> Widget {
+        // Columns fill from the top and rows from the left
+        let reverse = matches!((anchor, layout),
+            (Anchor::BottomLeft, Layout::Column(_)) | (Anchor::Bottom, Layout::Column(_)) | (Anchor::BottomRight, Layout::Column(_)) |
+            (Anchor::TopRight, Layout::Row(_)) | (Anchor::Right, Layout::Row(_)) | (Anchor::BottomRight, Layout::Row(_))
+        );
+
+        if reverse {
+            widgets.reverse();
+        }
+
+        Widget {
+            anchor: anchor,
+            offset: Point::new(MARGIN, MARGIN),
+            layout: layout,
+            children: widgets,
+            ..Widget::new()
+        }
+    }
+}
+
+impl Default for Widget {
+    fn default() -> Widget {
+        Widget::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::widget::*;
+
+    #[test]
+    fn test_layout() {
+        let widget = Widget::new().panel()
+            .anchor(Anchor::BottomRight, 10.0, 10.0)
+            .layout(Layout::Column(4.0))
+            .child(Widget::text("abcde"))
+            .child(Widget::bar(0.5, None).size(Size::Relative(1.0), Size::Fixed(8.0)));
+
+        let elements = widget.layout_on(1000.0, 500.0);
+        let size = widget.measure();
+
+        // Text, a gap and a bar, with padding all around
+        assert_eq!((size.x, size.y), (5.0 * CHAR_WIDTH + 24.0, LINE_HEIGHT + 4.0 + 8.0 + 24.0));
+
+        assert_eq!(elements.len(), 3);
+        assert_eq!(elements[0].content, Content::Panel);
+        assert_eq!((elements[0].rect.right(), elements[0].rect.bottom()), (990.0, 490.0));
+
+        // The bar is as wide as the text, right under it
+        assert_eq!(elements[2].rect.width, 5.0 * CHAR_WIDTH);
+        assert_eq!(elements[2].rect.y, elements[1].rect.bottom() + 4.0);
+    }
+}
@@ -0,0 +1,234 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::physics::{Circle,Point,Rectangle,Segment,Shape,Vector};
+use crate::storage::WorldArgs;
+
+// What happens to ships at the edge of the world
+#[derive(Clone,Copy,Debug,Default,Deserialize,PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Edge {
+    // Nothing, they fly on into the void
+    #[default]
+    Open,
+    // They bounce back in
+    Solid,
+    // They come back in on the other side, and everything near one edge
+    // is just as near the other
+    Wrap,
+}
+
+impl FromStr for Edge {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Edge, String> {
+        match s {
+            "open" => Ok(Edge::Open),
+            "solid" => Ok(Edge::Solid),
+            "wrap" => Ok(Edge::Wrap),
+            _ => Err(format!("Unknown edge: {}", s)),
+        }
+    }
+}
+
+// The part of the level that matters, from (0, 0) to its width and height
+#[derive(Clone,Copy,Debug)]
+pub struct World {
+    pub bounds: Rectangle,
+    pub edge: Edge,
+}
+
+impl World {
+    pub fn new(width: f64, height: f64, edge: Edge) -> World {
+        World {
+            bounds: Rectangle::new(0.0, 0.0, width, height),
+            edge: edge,
+        }
+    }
+
+    pub fn get_width(&self) -> f64 {
+        self.bounds.width
+    }
+
+    pub fn get_height(&self) -> f64 {
+        self.bounds.height
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        self.bounds.check_collision_point(&point)
+    }
+
+    // How far in from the closest edge, negative when outside
+    pub fn distance_to_edge(&self, point: Point) -> f64 {
+        f64::min(
+            f64::min(point.x - self.bounds.left(), self.bounds.right() - point.x),
+            f64::min(point.y - self.bounds.top(), self.bounds.bottom() - point.y)
+        )
+    }
+
+    // Keeps a ship inside, if the edges say so. True if it bounced.
+    pub fn confine(&self, circle: &mut Circle, vector: &mut Vector) -> bool {
+        match self.edge {
+            Edge::Open => false,
+            Edge::Solid => self.bounce(circle, vector),
+            Edge::Wrap => {
+                let point = self.wrap(Point::new(circle.x, circle.y));
+                circle.x = point.x;
+                circle.y = point.y;
+                false
+            },
+        }
+    }
+
+    pub fn is_wrapping(&self) -> bool {
+        self.edge == Edge::Wrap
+    }
+
+    // Back inside, if the world wraps around
+    pub fn wrap(&self, point: Point) -> Point {
+        if !self.is_wrapping() {
+            return point;
+        }
+
+        Point::new(
+            self.bounds.left() + (point.x - self.bounds.left()).rem_euclid(self.bounds.width),
+            self.bounds.top() + (point.y - self.bounds.top()).rem_euclid(self.bounds.height)
+        )
+    }
+
+    // The copy of `to` closest to `from`, which may be across an edge if
+    // the world wraps around. Aim there and the way is never the long one.
+    pub fn nearest(&self, from: Point, to: Point) -> Point {
+        if !self.is_wrapping() {
+            return to;
+        }
+
+        let dx = to.x - from.x;
+        let dy = to.y - from.y;
+
+        Point::new(
+            from.x + dx - self.bounds.width * (dx / self.bounds.width).round(),
+            from.y + dy - self.bounds.height * (dy / self.bounds.height).round()
+        )
+    }
+
+    pub fn nearest_circle(&self, from: Point, circle: Circle) -> Circle {
+        let p = self.nearest(from, Point::new(circle.x, circle.y));
+        Circle::new(p.x, p.y, circle.r)
+    }
+
+    pub fn distance(&self, from: Point, to: Point) -> f64 {
+        from.distance(self.nearest(from, to))
+    }
+
+    // The straight line from one point to the other, the short way round
+    pub fn sight_line(&self, from: Point, to: Point) -> Segment {
+        Segment::new(from, self.nearest(from, to))
+    }
+
+    // Whether a circle is in the way, wherever along the line it is
+    pub fn blocks(&self, line: &Segment, circle: &Circle) -> bool {
+        let middle = Point::new(
+            (line.point0.x + line.point1.x) / 2.0,
+            (line.point0.y + line.point1.y) / 2.0
+        );
+
+        line.check_collision_circle(&self.nearest_circle(middle, *circle))
+    }
+
+    // Everywhere a circle shows up in a rectangle, e.g. the screen, which
+    // for a wrapping world may be more than once
+    pub fn images(&self, circle: Circle, rect: &Rectangle) -> Vec<Circle> {
+        if !self.is_wrapping() {
+            return vec![circle];
+        }
+
+        // How many times the world fits before and after the rectangle
+        let [w, h] = [self.bounds.width, self.bounds.height];
+        let [x0, x1] = [((rect.left() - circle.right()) / w).ceil() as i64, ((rect.right() - circle.left()) / w).floor() as i64];
+        let [y0, y1] = [((rect.top() - circle.bottom()) / h).ceil() as i64, ((rect.bottom() - circle.top()) / h).floor() as i64];
+
+        let mut ret = Vec::new();
+
+        for i in x0..=x1 {
+            for j in y0..=y1 {
+                ret.push(Circle::new(circle.x + i as f64 * w, circle.y + j as f64 * h, circle.r));
+            }
+        }
+
+        ret
+    }
+
+    fn bounce(&self, circle: &mut Circle, vector: &mut Vector) -> bool {
+        let [mut dx, mut dy] = [vector.get_dx(), vector.get_dy()];
+        let mut bounced = false;
+
+        if circle.left() < self.bounds.left() || circle.right() > self.bounds.right() {
+            circle.x = circle.x.max(self.bounds.left() + circle.r).min(self.bounds.right() - circle.r);
+            dx = -dx;
+            bounced = true;
+        }
+
+        if circle.top() < self.bounds.top() || circle.bottom() > self.bounds.bottom() {
+            circle.y = circle.y.max(self.bounds.top() + circle.r).min(self.bounds.bottom() - circle.r);
+            dy = -dy;
+            bounced = true;
+        }
+
+        if bounced {
+            *vector = Vector::from_deltas(dx, dy);
+        }
+
+        bounced
+    }
+}
+
+impl From<&WorldArgs> for World {
+    fn from(args: &WorldArgs) -> World {
+        World::new(args.width, args.height, args.edge)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::physics::{Circle,Vector};
+    use crate::world::*;
+
+    #[test]
+    fn test_edges() {
+        let mut circle = Circle::new(995.0, 50.0, 10.0);
+        let mut vector = Vector::from_deltas(100.0, 10.0);
+
+        assert!(World::new(1000.0, 500.0, Edge::Solid).confine(&mut circle, &mut vector));
+        assert_eq!(circle.x, 990.0);
+        assert!(vector.get_dx() < 0.0 && vector.get_dy() > 0.0);
+
+        let mut circle = Circle::new(1010.0, -20.0, 10.0);
+        World::new(1000.0, 500.0, Edge::Wrap).confine(&mut circle, &mut vector);
+        assert_eq!((circle.x, circle.y), (10.0, 480.0));
+    }
+
+    #[test]
+    fn test_wrap() {
+        let world = World::new(1000.0, 500.0, Edge::Wrap);
+        let [a, b] = [Point::new(20.0, 250.0), Point::new(980.0, 250.0)];
+
+        // Across the edge rather than through the middle
+        assert_eq!(world.nearest(a, b), Point::new(-20.0, 250.0));
+        assert_eq!(world.distance(a, b), 40.0);
+        assert_eq!(World::new(1000.0, 500.0, Edge::Solid).distance(a, b), 960.0);
+
+        // An asteroid by the far edge blocks the short way round
+        let line = world.sight_line(a, b);
+        assert!(world.blocks(&line, &Circle::new(995.0, 250.0, 10.0)));
+        assert!(!world.blocks(&line, &Circle::new(500.0, 250.0, 10.0)));
+
+        // Seen once by a screen straddling the edge, from either side, and
+        // twice by one wider than the world
+        let screen = Rectangle::new(-200.0, 0.0, 400.0, 500.0);
+        assert_eq!(world.images(Circle::new(5.0, 250.0, 10.0), &screen).len(), 1);
+        assert_eq!(world.images(Circle::new(995.0, 250.0, 10.0), &screen).len(), 1);
+        assert_eq!(world.images(Circle::new(5.0, 250.0, 10.0), &Rectangle::new(-200.0, 0.0, 1200.0, 500.0)).len(), 2);
+    }
+}